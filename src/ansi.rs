@@ -0,0 +1,281 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! A GridDisplay that draws to a terminal with 24-bit colour escape codes,
+//! for playing without a launchpad, i.e. over SSH.
+
+use crate::{Button, Color, GridDisplay, Pad, Status};
+use array2d::Array2D;
+use std::io::{self, Write};
+
+/// Draws the grid as coloured blocks, with the round buttons around it
+/// and the score, level and next piece beside it.
+/// Only what's changed since the last frame is redrawn.
+pub struct TerminalDisplay<W: Write = io::Stdout> {
+    out: W,
+    width: usize,
+    height: usize,
+    /// What each pad is showing, indexed the same as the matrix
+    shown: Array2D<Color>,
+    /// Last status shown, so unchanged panels aren't redrawn
+    status: Option<Status>,
+}
+
+/// Columns the status panels start at, after the grid and scene buttons
+const PANEL_GAP: usize = 4;
+
+// Core defs
+impl TerminalDisplay {
+    /// Takes over the terminal on stdout with a grid of the given size.
+    /// It's given back when the display is dropped.
+    pub fn new(width: usize, height: usize) -> io::Result<TerminalDisplay> {
+        TerminalDisplay::with_writer(io::stdout(), width, height)
+    }
+}
+impl<W: Write> TerminalDisplay<W> {
+    /// Same as new, but draws to any writer instead of stdout
+    pub fn with_writer(out: W, width: usize, height: usize) -> io::Result<TerminalDisplay<W>> {
+        let mut display = TerminalDisplay {
+            out,
+            width,
+            height,
+            shown: Array2D::filled_with(Color::OFF, height, width),
+            status: None,
+        };
+        // Clear the screen and hide the cursor
+        write!(display.out, "\x1b[2J\x1b[?25l")?;
+        for y in 0..height {
+            for x in 0..width {
+                display.draw_pad(x, y, Color::OFF)?;
+            }
+        }
+        for n in 0..width.max(height) {
+            display.draw_button(Button::Top(n as u8), Color::OFF)?;
+            display.draw_button(Button::Scene(n as u8), Color::OFF)?;
+        }
+        display.out.flush()?;
+        Ok(display)
+    }
+    /// Returns what's been written to the terminal
+    pub fn writer(&self) -> &W {
+        &self.out
+    }
+}
+// Render defs
+impl<W: Write> TerminalDisplay<W> {
+    /// Moves the cursor to a 1-based (row, column)
+    fn goto(&mut self, row: usize, column: usize) -> io::Result<()> {
+        write!(self.out, "\x1b[{};{}H", row, column)
+    }
+    /// Draws a single pad, y is bottom to top like Pad.y.
+    /// Each pad is two columns wide so they come out roughly square.
+    fn draw_pad(&mut self, x: usize, y: usize, color: Color) -> io::Result<()> {
+        // Row 1 is the top buttons
+        self.goto(self.height - y + 1, 2 * x + 1)?;
+        match color {
+            Color::OFF => write!(self.out, "\x1b[38;2;60;60;60m· \x1b[0m"),
+            color => {
+                let (r, g, b) = color.to_rgb8();
+                write!(self.out, "\x1b[48;2;{};{};{}m  \x1b[0m", r, g, b)
+            }
+        }
+    }
+    /// Draws one of the round buttons as a dot above or to the right of the grid
+    fn draw_button(&mut self, button: Button, color: Color) -> io::Result<()> {
+        match button {
+            Button::Grid(pad) => return self.draw_pad(pad.x as usize, pad.y as usize, color),
+            Button::Top(n) if (n as usize) < self.width => self.goto(1, 2 * n as usize + 1)?,
+            Button::Scene(n) if (n as usize) < self.height => {
+                self.goto(self.height - n as usize + 1, 2 * self.width + 2)?
+            }
+            _ => return Ok(()),
+        }
+        let (r, g, b) = match color {
+            Color::OFF => (60, 60, 60),
+            color => color.to_rgb8(),
+        };
+        write!(self.out, "\x1b[38;2;{};{};{}m●\x1b[0m", r, g, b)
+    }
+    /// Draws a line of the status panel, clearing whatever was there before
+    fn draw_panel_line(&mut self, row: usize, text: &str) -> io::Result<()> {
+        self.goto(row, 2 * self.width + PANEL_GAP)?;
+        write!(self.out, "{}\x1b[K", text)
+    }
+    /// Draws a frame, only touching the pads that changed
+    fn draw_matrix(&mut self, matrix: &Array2D<Color>) -> io::Result<()> {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = matrix.get(y, x).copied().unwrap_or(Color::OFF);
+                if self.shown[(y, x)] != color {
+                    self.shown[(y, x)] = color;
+                    self.draw_pad(x, y, color)?;
+                }
+            }
+        }
+        self.out.flush()
+    }
+}
+impl<W: Write> Drop for TerminalDisplay<W> {
+    /// Puts the cursor back, below everything that was drawn
+    fn drop(&mut self) {
+        let row = self.height + 4;
+        self.goto(row, 1).ok();
+        write!(self.out, "\x1b[0m\x1b[?25h").ok();
+        self.out.flush().ok();
+    }
+}
+impl<W: Write> GridDisplay for TerminalDisplay<W> {
+    type Error = io::Error;
+
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+    fn send_matrix(&mut self, matrix: Array2D<u8>) -> io::Result<()> {
+        let colors: Vec<Color> = matrix
+            .elements_row_major_iter()
+            .map(|index| Color::Palette(*index))
+            .collect();
+        let colors = Array2D::from_row_major(&colors, matrix.num_rows(), matrix.num_columns());
+        self.draw_matrix(&colors)
+    }
+    fn send_pad(&mut self, pad: Pad, velocity: u8) -> io::Result<()> {
+        self.send_pad_color(pad, Color::Palette(velocity))
+    }
+    fn send_button(&mut self, button: Button, velocity: u8) -> io::Result<()> {
+        match button {
+            Button::Grid(pad) => self.send_pad(pad, velocity),
+            button => {
+                self.draw_button(button, Color::Palette(velocity))?;
+                self.out.flush()
+            }
+        }
+    }
+    fn send_color_matrix(&mut self, matrix: Array2D<Color>) -> io::Result<()> {
+        self.draw_matrix(&matrix)
+    }
+    fn send_pad_color(&mut self, pad: Pad, color: Color) -> io::Result<()> {
+        let (x, y) = (pad.x as usize, pad.y as usize);
+        if x < self.width && y < self.height {
+            self.shown[(y, x)] = color;
+            self.draw_pad(x, y, color)?;
+        }
+        self.out.flush()
+    }
+    /// Shows the text on the line under the grid, it doesn't actually scroll
+    fn scroll_text(
+        &mut self,
+        text: &str,
+        velocity: u8,
+        _speed: u8,
+        _looping: bool,
+    ) -> io::Result<()> {
+        let (r, g, b) = Color::Palette(velocity).to_rgb8();
+        let row = self.height + 2;
+        self.goto(row, 1)?;
+        write!(
+            self.out,
+            "\x1b[38;2;{};{};{}m{}\x1b[0m\x1b[K",
+            r, g, b, text
+        )?;
+        self.out.flush()
+    }
+    fn show_status(&mut self, status: &Status) -> io::Result<()> {
+        let last = self.status.take();
+        if last.as_ref().map(|last| last.score) != Some(status.score) {
+            self.draw_panel_line(2, "SCORE")?;
+            self.draw_panel_line(3, &status.score.to_string())?;
+        }
+        if last.as_ref().map(|last| last.level) != Some(status.level) {
+            self.draw_panel_line(5, "LEVEL")?;
+            self.draw_panel_line(6, &status.level.to_string())?;
+        }
+        if last.as_ref().map(|last| last.lines) != Some(status.lines) {
+            self.draw_panel_line(8, "LINES")?;
+            self.draw_panel_line(9, &status.lines.to_string())?;
+        }
+        if last.as_ref().map(|last| &last.next) != Some(&status.next) {
+            self.draw_panel_line(11, "NEXT")?;
+            // The next piece, top row first
+            for row in 0..4 {
+                let mut line = String::new();
+                for x in 0..4 {
+                    let y = status.next.num_rows().checked_sub(row + 1);
+                    match y.and_then(|y| status.next.get(y, x)) {
+                        Some(index) if *index != 0 => {
+                            let (r, g, b) = Color::Palette(*index).to_rgb8();
+                            line += &format!("\x1b[48;2;{};{};{}m  \x1b[0m", r, g, b);
+                        }
+                        _ => line += "  ",
+                    }
+                }
+                self.draw_panel_line(12 + row, &line)?;
+            }
+        }
+        self.status = Some(status.clone());
+        self.out.flush()
+    }
+    /// Turns every pad off and clears the message line
+    fn clear(&mut self) -> io::Result<()> {
+        self.draw_matrix(&Array2D::filled_with(Color::OFF, self.height, self.width))?;
+        for n in 0..self.width.max(self.height) {
+            self.draw_button(Button::Top(n as u8), Color::OFF)?;
+            self.draw_button(Button::Scene(n as u8), Color::OFF)?;
+        }
+        let row = self.height + 2;
+        self.goto(row, 1)?;
+        write!(self.out, "\x1b[K")?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TerminalDisplay;
+    use crate::{Color, GridDisplay, Pad};
+    use array2d::Array2D;
+
+    fn output(display: &mut TerminalDisplay<Vec<u8>>) -> String {
+        let out = String::from_utf8(display.writer().clone()).unwrap();
+        display.out.clear();
+        out
+    }
+
+    #[test]
+    fn only_changes_drawn() {
+        let mut display = TerminalDisplay::with_writer(Vec::new(), 8, 8).unwrap();
+        output(&mut display);
+        let mut matrix = Array2D::filled_with(0, 8, 8);
+        display.send_matrix(matrix.clone()).unwrap();
+        assert_eq!(output(&mut display), "");
+        matrix.set(0, 0, 5).unwrap();
+        display.send_matrix(matrix).unwrap();
+        // Bottom left pad, red
+        assert_eq!(output(&mut display), "\x1b[9;1H\x1b[48;2;255;0;0m  \x1b[0m");
+    }
+    #[test]
+    fn rgb() {
+        let mut display = TerminalDisplay::with_writer(Vec::new(), 8, 8).unwrap();
+        output(&mut display);
+        display
+            .send_pad_color(Pad { x: 7, y: 7 }, Color::Rgb(63, 0, 21))
+            .unwrap();
+        assert_eq!(
+            output(&mut display),
+            "\x1b[2;15H\x1b[48;2;255;0;85m  \x1b[0m"
+        );
+    }
+}
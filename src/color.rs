@@ -0,0 +1,112 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+use crate::palette;
+
+/// A colour a pad can be lit with
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Color {
+    /// An index into the device's built in 128 colour palette
+    Palette(u8),
+    /// Red, green and blue, each 0-63
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Unlit
+    pub const OFF: Color = Color::Palette(0);
+    /// Returns an RGB colour, with each channel clamped to 0-63
+    /// ```
+    /// # use lp_tetris::Color;
+    /// assert_eq!(Color::rgb(10, 200, 63), Color::Rgb(10, 63, 63));
+    /// ```
+    pub fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color::Rgb(r.min(63), g.min(63), b.min(63))
+    }
+    /// Returns the colour scaled towards black, where 1.0 is unchanged and 0.0 is off.
+    /// Only RGB colours can be dimmed, palette colours are returned as they are.
+    /// ```
+    /// # use lp_tetris::Color;
+    /// assert_eq!(Color::Rgb(63, 20, 0).dimmed(0.5), Color::Rgb(32, 10, 0));
+    /// assert_eq!(Color::Palette(5).dimmed(0.5), Color::Palette(5));
+    /// ```
+    pub fn dimmed(self, brightness: f32) -> Color {
+        match self {
+            Color::Rgb(r, g, b) => {
+                let scale = |c: u8| (f32::from(c) * brightness.clamp(0.0, 1.0)).round() as u8;
+                Color::Rgb(scale(r), scale(g), scale(b))
+            }
+            palette => palette,
+        }
+    }
+    /// Returns what the colour looks like, as 8-bit (red, green, blue)
+    /// ```
+    /// # use lp_tetris::Color;
+    /// assert_eq!(Color::Rgb(63, 0, 21).to_rgb8(), (255, 0, 85));
+    /// assert_eq!(Color::Palette(5).to_rgb8(), (255, 0, 0));
+    /// ```
+    pub fn to_rgb8(self) -> (u8, u8, u8) {
+        match self {
+            Color::Rgb(r, g, b) => {
+                let scale = |c: u8| (u16::from(c.min(63)) * 255 / 63) as u8;
+                (scale(r), scale(g), scale(b))
+            }
+            Color::Palette(index) => palette::rgb(index),
+        }
+    }
+    /// Returns the palette index closest to this colour, for displays without RGB
+    /// ```
+    /// # use lp_tetris::{palette, Color};
+    /// assert_eq!(Color::Rgb(0, 0, 63).to_palette(), palette::BLUE);
+    /// assert_eq!(Color::Palette(9).to_palette(), 9);
+    /// ```
+    pub fn to_palette(self) -> u8 {
+        match self {
+            Color::Palette(index) => index,
+            rgb => {
+                let (r, g, b) = rgb.to_rgb8();
+                palette::nearest(r, g, b)
+            }
+        }
+    }
+    /// Returns a colour part way between this one and another, for fades.
+    /// `t` of 0.0 is this colour, 1.0 is the other one.
+    /// Only works between RGB colours, otherwise whichever is closer is returned.
+    /// ```
+    /// # use lp_tetris::Color;
+    /// assert_eq!(Color::Rgb(0, 0, 0).blend(Color::Rgb(63, 32, 0), 0.5), Color::Rgb(32, 16, 0));
+    /// ```
+    pub fn blend(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        match (self, other) {
+            (Color::Rgb(r1, g1, b1), Color::Rgb(r2, g2, b2)) => {
+                let mix =
+                    |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+                Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+            }
+            _ if t < 0.5 => self,
+            _ => other,
+        }
+    }
+}
+
+impl From<u8> for Color {
+    /// Bare u8s are palette indexes, same as everywhere else in the crate
+    fn from(index: u8) -> Color {
+        Color::Palette(index)
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+use crate::{input::Bindings, record::Recorder, Button, Color, ControlEvent, InputEvent, Pad};
+use array2d::Array2D;
+
+/// What the game has to say besides the grid
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+    pub score: u32,
+    pub level: u32,
+    pub lines: u32,
+    /// The next piece in its colour, rows bottom to top like the frame
+    pub next: Array2D<u8>,
+}
+
+/// Something the game can draw its matrix onto.
+/// Launchpad implements this, but so can anything else with a grid of pixels,
+/// which lets the game run without the hardware plugged in.
+pub trait GridDisplay {
+    /// What goes wrong when sending to the display
+    type Error;
+    /// Returns the (width, height) of the grid, which send_matrix expects frames to be
+    fn size(&self) -> (usize, usize) {
+        (8, 8)
+    }
+    /// Sends a whole frame to the display.
+    /// Rows are indexed bottom to top, same as Pad.y
+    fn send_matrix(&mut self, matrix: Array2D<u8>) -> Result<(), Self::Error>;
+    /// Sets a single pad to the given palette colour
+    fn send_pad(&mut self, pad: Pad, velocity: u8) -> Result<(), Self::Error>;
+    /// Lights any button in the given palette colour.
+    /// Displays with nothing but a grid only need to handle Button::Grid.
+    fn send_button(&mut self, button: Button, velocity: u8) -> Result<(), Self::Error> {
+        match button {
+            Button::Grid(pad) => self.send_pad(pad, velocity),
+            _ => Ok(()),
+        }
+    }
+    /// Same as send_matrix, but each pad can be a palette or RGB colour
+    fn send_color_matrix(&mut self, matrix: Array2D<Color>) -> Result<(), Self::Error>;
+    /// Same as send_pad, but the colour can be a palette or RGB colour
+    fn send_pad_color(&mut self, pad: Pad, color: Color) -> Result<(), Self::Error>;
+    /// Scrolls a message across the display in the given palette colour.
+    /// Speed is 1 (slow) to 7 (fast). Displays that can't show text can ignore this.
+    fn scroll_text(
+        &mut self,
+        _text: &str,
+        _velocity: u8,
+        _speed: u8,
+        _looping: bool,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Returns whether text sent with scroll_text is still going.
+    /// Anything sent to the display while this is true will overwrite the text.
+    fn is_scrolling(&self) -> bool {
+        false
+    }
+    /// Shows the score, level and next piece, for displays with room for them
+    fn show_status(&mut self, _status: &Status) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Turns every pad off
+    fn clear(&mut self) -> Result<(), Self::Error>;
+}
+
+/// A display with controls on it, that the game can be played on by itself.
+/// Launchpad and MultiLaunchpad both implement this.
+pub trait Controller: GridDisplay {
+    /// Gets the next InputEvent, presses and releases both
+    fn poll_event(&mut self) -> Option<InputEvent>;
+    /// Gets the next ControlEvent, skipping releases
+    fn poll_input(&mut self) -> Option<ControlEvent> {
+        loop {
+            match self.poll_event()? {
+                InputEvent {
+                    event,
+                    pressed: true,
+                    ..
+                } => return Some(event),
+                _ => continue,
+            }
+        }
+    }
+    /// Pulses every pad in the matrix that isn't 0
+    fn pulse_matrix(&mut self, matrix: Array2D<u8>) -> Result<(), Self::Error>;
+    /// Stops any scrolling text
+    fn stop_scroll(&mut self) -> Result<(), Self::Error>;
+    /// Replaces what the buttons (and keyboard) do
+    fn set_bindings(&mut self, bindings: Bindings);
+    /// Records MIDI traffic, or stops if given None
+    fn set_recorder(&mut self, recorder: Option<Recorder>);
+}
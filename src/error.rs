@@ -0,0 +1,54 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+use crate::Button;
+use std::{error::Error, fmt};
+
+/// Everything that can go wrong talking to a launchpad
+#[derive(Clone, Debug, PartialEq)]
+pub enum LaunchpadError {
+    /// The system MIDI client couldn't be created
+    MidiInit(String),
+    /// No supported launchpad was found, or none matched the requested name or index
+    NoDevice,
+    /// The port was found but couldn't be opened, usually because something else is using it
+    PortBusy(String),
+    /// A message couldn't be sent, usually because the device was unplugged
+    SendFailed(String),
+    /// The launchpad's input port couldn't be found or opened
+    InputInit(String),
+    /// A button was sent that isn't on the launchpad, i.e. Button::Top(8)
+    NoSuchButton(Button),
+    /// Launchpads can't be tiled as asked, i.e. the rows aren't all the same length
+    BadLayout(String),
+}
+
+impl fmt::Display for LaunchpadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LaunchpadError::MidiInit(e) => write!(f, "couldn't initialise MIDI: {}", e),
+            LaunchpadError::NoDevice => write!(f, "couldn't find a launchpad"),
+            LaunchpadError::PortBusy(port) => write!(f, "couldn't open port {}", port),
+            LaunchpadError::SendFailed(e) => write!(f, "couldn't send to launchpad: {}", e),
+            LaunchpadError::InputInit(e) => write!(f, "couldn't open launchpad input: {}", e),
+            LaunchpadError::NoSuchButton(button) => write!(f, "there's no {:?} to light", button),
+            LaunchpadError::BadLayout(e) => write!(f, "can't tile launchpads like that: {}", e),
+        }
+    }
+}
+
+impl Error for LaunchpadError {}
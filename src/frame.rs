@@ -0,0 +1,122 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+use crate::Pad;
+use array2d::Array2D;
+
+/// Remembers the last frame sent to a device, so the next one only needs to send what changed
+#[derive(Debug, Clone, Default)]
+pub struct FrameBuffer {
+    /// The last frame sent, None if nothing has been
+    last: Option<Array2D<u8>>,
+    /// Whether the device is known to be showing last
+    synced: bool,
+}
+
+impl FrameBuffer {
+    /// Returns a buffer that doesn't know what the device is showing,
+    /// so the first frame is sent in full
+    pub fn new() -> FrameBuffer {
+        FrameBuffer::default()
+    }
+    /// Returns the pads that differ from the last frame and remembers this one.
+    /// Every pad is returned if the last frame is unknown.
+    /// ```
+    /// # use lp_tetris::{FrameBuffer, Pad};
+    /// # use array2d::Array2D;
+    /// let mut frame = FrameBuffer::new();
+    /// assert_eq!(frame.diff(Array2D::filled_with(0, 8, 8)).len(), 64);
+    /// let mut next = Array2D::filled_with(0, 8, 8);
+    /// next.set(2, 1, 5).unwrap();
+    /// assert_eq!(frame.diff(next.clone()), vec![(Pad { x: 1, y: 2 }, 5)]);
+    /// assert_eq!(frame.diff(next), vec![]);
+    /// ```
+    pub fn diff(&mut self, frame: Array2D<u8>) -> Vec<(Pad, u8)> {
+        let mut changed = Vec::new();
+        for y in 0..frame.num_rows() {
+            for x in 0..frame.num_columns() {
+                let color = frame[(y, x)];
+                let last = match &self.last {
+                    Some(last) if self.synced => last.get(y, x),
+                    _ => None,
+                };
+                if last != Some(&color) {
+                    changed.push((
+                        Pad {
+                            x: x as u8,
+                            y: y as u8,
+                        },
+                        color,
+                    ));
+                }
+            }
+        }
+        self.last = Some(frame);
+        self.synced = true;
+        changed
+    }
+    /// Records a single pad being set outside of diff.
+    /// Does nothing if nothing has been sent yet, or the pad isn't on the grid.
+    pub fn set(&mut self, pad: Pad, color: u8) {
+        if let Some(last) = self.last.as_mut() {
+            last.set(pad.y as usize, pad.x as usize, color).ok();
+        }
+    }
+    /// Records every pad being set to the same colour
+    pub fn fill(&mut self, color: u8) {
+        self.last = Some(Array2D::filled_with(color, 8, 8));
+        self.synced = true;
+    }
+    /// Marks the device as no longer showing the last frame, so the next one is sent in full.
+    /// Use this when something else has drawn over the grid.
+    pub fn invalidate(&mut self) {
+        self.synced = false;
+    }
+    /// Returns the last frame sent, even if the device isn't showing it anymore
+    pub fn last(&self) -> Option<&Array2D<u8>> {
+        self.last.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameBuffer;
+    use crate::Pad;
+    use array2d::Array2D;
+
+    #[test]
+    fn set_and_fill() {
+        let mut frame = FrameBuffer::new();
+        frame.set(Pad { x: 0, y: 0 }, 5);
+        assert_eq!(frame.last(), None);
+        frame.fill(0);
+        frame.set(Pad { x: 3, y: 4 }, 5);
+        frame.set(Pad { x: 8, y: 4 }, 5);
+        let mut expected = Array2D::filled_with(0, 8, 8);
+        expected.set(4, 3, 5).unwrap();
+        assert_eq!(frame.last(), Some(&expected));
+        assert_eq!(frame.diff(expected), vec![]);
+    }
+    #[test]
+    fn invalidate() {
+        let mut frame = FrameBuffer::new();
+        frame.diff(Array2D::filled_with(1, 8, 8));
+        frame.invalidate();
+        assert_eq!(frame.last(), Some(&Array2D::filled_with(1, 8, 8)));
+        assert_eq!(frame.diff(Array2D::filled_with(1, 8, 8)).len(), 64);
+    }
+}
@@ -0,0 +1,389 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Keyboard and gamepad input backends.
+//! Each backend reads keys from somewhere platform specific,
+//! turns them into a Key or GamepadButton, and sends whatever the Bindings map it to down a channel.
+
+use crate::InputEvent;
+use std::{env, io, sync::mpsc, time::Instant};
+
+mod bindings;
+pub use bindings::{Bindings, BindingsError, SharedBindings, Trigger, DEFAULT_BINDINGS};
+
+#[cfg(target_os = "linux")]
+pub mod evdev;
+pub mod gamepad;
+#[cfg(windows)]
+pub mod rawinput;
+#[cfg(unix)]
+pub mod terminal;
+
+/// A physical key, independent of whichever backend read it
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Zero,
+    One,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Left,
+    Right,
+    Up,
+    Down,
+    Space,
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+}
+
+impl Key {
+    /// Every key, for looking them up by name
+    pub const ALL: [Key; 45] = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+        Key::Zero,
+        Key::One,
+        Key::Two,
+        Key::Three,
+        Key::Four,
+        Key::Five,
+        Key::Six,
+        Key::Seven,
+        Key::Eight,
+        Key::Nine,
+        Key::Left,
+        Key::Right,
+        Key::Up,
+        Key::Down,
+        Key::Space,
+        Key::Enter,
+        Key::Escape,
+        Key::Backspace,
+        Key::Tab,
+    ];
+    /// Parses a key from its name, ignoring case
+    /// ```
+    /// # use lp_tetris::input::Key;
+    /// assert_eq!(Key::from_name("backspace"), Some(Key::Backspace));
+    /// assert_eq!(Key::from_name("Q"), Some(Key::Q));
+    /// assert_eq!(Key::from_name("Meta"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Key> {
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+    }
+}
+
+/// A gamepad button, named by position rather than label since every brand labels them differently
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum GamepadButton {
+    /// A on xbox pads, cross on playstation
+    South,
+    East,
+    West,
+    North,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftShoulder,
+    RightShoulder,
+    Select,
+    Start,
+}
+
+impl GamepadButton {
+    /// Every button, for looking them up by name
+    pub const ALL: [GamepadButton; 12] = [
+        GamepadButton::South,
+        GamepadButton::East,
+        GamepadButton::West,
+        GamepadButton::North,
+        GamepadButton::DPadUp,
+        GamepadButton::DPadDown,
+        GamepadButton::DPadLeft,
+        GamepadButton::DPadRight,
+        GamepadButton::LeftShoulder,
+        GamepadButton::RightShoulder,
+        GamepadButton::Select,
+        GamepadButton::Start,
+    ];
+    /// Parses a button from its name, ignoring case
+    pub fn from_name(name: &str) -> Option<GamepadButton> {
+        GamepadButton::ALL
+            .iter()
+            .copied()
+            .find(|button| format!("{:?}", button).eq_ignore_ascii_case(name))
+    }
+}
+
+/// Looks up a trigger in bindings shared with the game,
+/// returning the press or release of whatever it's bound to, happening now
+pub(crate) fn lookup(
+    bindings: &SharedBindings,
+    trigger: Trigger,
+    pressed: bool,
+) -> Option<InputEvent> {
+    let event = match bindings.read() {
+        Ok(bindings) => bindings.get(trigger),
+        Err(poisoned) => poisoned.into_inner().get(trigger),
+    }?;
+    Some(InputEvent {
+        event,
+        pressed,
+        time: Instant::now(),
+    })
+}
+
+/// The places keyboard input can be read from
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Backend {
+    /// Windows raw input, via multiinput
+    RawInput,
+    /// Linux /dev/input/event* devices. Needs read access to them (usually the `input` group)
+    Evdev,
+    /// The controlling terminal, in raw mode. Works over SSH, but can't see key releases
+    Terminal,
+}
+
+impl Backend {
+    /// Parses a backend name, as used in LP_TETRIS_INPUT
+    pub fn from_name(name: &str) -> Option<Backend> {
+        match name {
+            "rawinput" => Some(Backend::RawInput),
+            "evdev" => Some(Backend::Evdev),
+            "terminal" => Some(Backend::Terminal),
+            _ => None,
+        }
+    }
+    /// Picks a backend for the machine we're running on.
+    /// LP_TETRIS_INPUT=rawinput|evdev|terminal overrides the choice.
+    /// Otherwise evdev is preferred when a keyboard can be opened, falling back to the terminal.
+    /// Over SSH the terminal wins, the keyboards plugged into the machine aren't the player's.
+    pub fn detect() -> Option<Backend> {
+        if let Ok(name) = env::var("LP_TETRIS_INPUT") {
+            return Backend::from_name(&name);
+        }
+        if cfg!(windows) {
+            return Some(Backend::RawInput);
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let remote = stdin_is_tty() && over_ssh();
+            if !remote && !evdev::readable_keyboards().is_empty() {
+                return Some(Backend::Evdev);
+            }
+        }
+        if stdin_is_tty() {
+            return Some(Backend::Terminal);
+        }
+        None
+    }
+}
+
+/// Returns whether stdin is a terminal the terminal backend can read
+fn stdin_is_tty() -> bool {
+    #[cfg(unix)]
+    {
+        terminal::is_tty()
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+/// Returns whether we're running in an SSH session
+#[allow(unused)]
+fn over_ssh() -> bool {
+    env::var_os("SSH_CONNECTION").is_some() || env::var_os("SSH_TTY").is_some()
+}
+
+/// Starts reading every gamepad that's plugged in, each on a new thread,
+/// sending every bound button press and release down `events_tx`. Returns how many were found.
+/// On Windows gamepads are read by the RawInput backend along with the keyboard,
+/// so this doesn't find any.
+pub fn spawn_gamepads(
+    events_tx: mpsc::Sender<InputEvent>,
+    bindings: SharedBindings,
+) -> io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        evdev::spawn_gamepads(events_tx, bindings)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (events_tx, bindings);
+        Ok(0)
+    }
+}
+
+/// Keeps an input backend alive.
+/// Dropping it puts back anything the backend changed, i.e. the terminal mode.
+#[derive(Default)]
+pub struct InputHandle {
+    #[cfg(unix)]
+    _raw_mode: Option<terminal::RawMode>,
+}
+
+/// Picks a backend with Backend::detect and starts reading from it.
+/// If it was picked automatically and won't start, the terminal is tried instead.
+/// Returns the backend that's being read.
+pub fn spawn_detected(
+    events_tx: mpsc::Sender<InputEvent>,
+    bindings: SharedBindings,
+) -> io::Result<(Backend, InputHandle)> {
+    let backend = Backend::detect()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no keyboard input available"))?;
+    let chosen = env::var_os("LP_TETRIS_INPUT").is_some();
+    with_fallback(backend, !chosen && stdin_is_tty(), |backend| {
+        spawn(backend, events_tx.clone(), bindings.clone())
+    })
+}
+
+/// Starts `backend` with `start`, trying the terminal instead if that fails and `fall_back` is set
+fn with_fallback<T>(
+    backend: Backend,
+    fall_back: bool,
+    mut start: impl FnMut(Backend) -> io::Result<T>,
+) -> io::Result<(Backend, T)> {
+    match start(backend) {
+        Ok(handle) => Ok((backend, handle)),
+        Err(e) if fall_back && backend != Backend::Terminal => {
+            eprintln!(
+                "Couldn't read keyboard input via {:?}: {}, using the terminal",
+                backend, e
+            );
+            start(Backend::Terminal).map(|handle| (Backend::Terminal, handle))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Starts reading from the given backend on a new thread,
+/// sending every bound key press and release down `events_tx`.
+pub fn spawn(
+    backend: Backend,
+    events_tx: mpsc::Sender<InputEvent>,
+    bindings: SharedBindings,
+) -> io::Result<InputHandle> {
+    match backend {
+        #[cfg(windows)]
+        Backend::RawInput => {
+            rawinput::spawn(events_tx, bindings)?;
+            Ok(InputHandle::default())
+        }
+        #[cfg(target_os = "linux")]
+        Backend::Evdev => {
+            evdev::spawn(events_tx, bindings)?;
+            Ok(InputHandle::default())
+        }
+        #[cfg(unix)]
+        Backend::Terminal => Ok(InputHandle {
+            _raw_mode: Some(terminal::spawn(events_tx, bindings)?),
+        }),
+        #[allow(unreachable_patterns)]
+        _ => Err(io::Error::other(format!(
+            "{:?} input isn't supported on this platform",
+            backend
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{with_fallback, Backend};
+    use std::io;
+
+    fn evdev_denied(backend: Backend) -> io::Result<Backend> {
+        match backend {
+            Backend::Evdev => Err(io::Error::new(io::ErrorKind::PermissionDenied, "no access")),
+            backend => Ok(backend),
+        }
+    }
+
+    #[test]
+    fn falls_back_to_terminal() {
+        let (backend, started) = with_fallback(Backend::Evdev, true, evdev_denied).unwrap();
+        assert_eq!((backend, started), (Backend::Terminal, Backend::Terminal));
+        // Nothing to fall back to without a terminal, or when evdev was asked for
+        let err = with_fallback(Backend::Evdev, false, evdev_denied).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        // Backends that start are kept
+        let (backend, _) = with_fallback(Backend::RawInput, true, evdev_denied).unwrap();
+        assert_eq!(backend, Backend::RawInput);
+    }
+}
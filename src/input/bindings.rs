@@ -0,0 +1,322 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Which keys and buttons do what, loaded from a file so they can be changed without recompiling.
+//!
+//! The file is split into profiles, one per player, each a `[name]` header
+//! followed by `action = trigger, trigger, ...` lines. `#` starts a comment.
+//! Profiles start from `[default]`, which starts from the built-in bindings,
+//! so a profile only needs the actions it changes. An action with nothing after
+//! the `=` is unbound.
+//!
+//! Actions are rotate_left, rotate_right, move_left, move_right, move_up, move_down,
+//! drop and exit. Triggers are key names as in Key (`A`, `Left`, `Space`...),
+//! launchpad buttons as `top:N`, `scene:N` (both 0-7, scene 0 at the bottom) or `pad:X:Y`,
+//! and gamepad buttons as `gamepad:` and a GamepadButton name.
+
+use super::{GamepadButton, Key};
+use crate::{Button, ControlEvent, Pad};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs, io,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+/// The bindings used when there's no file, in the same format as the file
+pub const DEFAULT_BINDINGS: &str = "\
+[default]
+rotate_left = A, scene:6, gamepad:West
+rotate_right = D, scene:7, gamepad:South
+move_left = Left, top:2, gamepad:DPadLeft
+move_right = Right, top:3, gamepad:DPadRight
+move_up = Up, top:0, gamepad:DPadUp
+move_down = Down, top:1, gamepad:DPadDown
+drop = Space, scene:0, gamepad:North
+exit = Backspace, top:7, gamepad:Select
+";
+
+/// Bindings shared between the game and the threads reading input,
+/// so they can be changed while it's running
+pub type SharedBindings = Arc<RwLock<Bindings>>;
+
+/// One `action = triggers` line of a bindings file
+type Binding = (ControlEvent, Vec<Trigger>);
+
+/// Something the player can press
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Trigger {
+    Key(Key),
+    /// A launchpad's pad or round button
+    Button(Button),
+    Gamepad(GamepadButton),
+}
+
+/// Maps triggers to the ControlEvents they send
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bindings {
+    map: HashMap<Trigger, ControlEvent>,
+}
+
+/// Why a bindings file couldn't be loaded
+#[derive(Debug)]
+pub enum BindingsError {
+    /// The file couldn't be read
+    Io(io::Error),
+    /// A line of the file didn't make sense, lines count from 1
+    Parse { line: usize, message: String },
+    /// The file doesn't have the requested profile
+    NoProfile(String),
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BindingsError::Io(e) => write!(f, "couldn't read bindings: {}", e),
+            BindingsError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            BindingsError::NoProfile(name) => write!(f, "no profile called [{}]", name),
+        }
+    }
+}
+
+impl Error for BindingsError {}
+
+impl Default for Bindings {
+    /// The built-in bindings, from DEFAULT_BINDINGS
+    fn default() -> Bindings {
+        Bindings::parse_onto(Bindings::empty(), DEFAULT_BINDINGS, "default")
+            .expect("DEFAULT_BINDINGS is invalid")
+    }
+}
+
+impl Bindings {
+    /// Returns bindings that don't do anything
+    pub fn empty() -> Bindings {
+        Bindings {
+            map: HashMap::new(),
+        }
+    }
+    /// Reads the given profile from a bindings file
+    pub fn load<P: AsRef<Path>>(path: P, profile: &str) -> Result<Bindings, BindingsError> {
+        let text = fs::read_to_string(path).map_err(BindingsError::Io)?;
+        Bindings::parse(&text, profile)
+    }
+    /// Reads the given profile from the text of a bindings file.
+    /// Every profile is checked, not just the one asked for.
+    /// ```
+    /// # use lp_tetris::{input::{Bindings, Key, Trigger}, Button, ControlEvent};
+    /// let text = "[default]\ndrop = Space, Enter\n[player2]\nmove_left = J\n";
+    /// let bindings = Bindings::parse(text, "player2").unwrap();
+    /// assert_eq!(bindings.get(Trigger::Key(Key::J)), Some(ControlEvent::MoveLeft));
+    /// assert_eq!(bindings.get(Trigger::Key(Key::Left)), None);
+    /// assert_eq!(bindings.get(Trigger::Key(Key::Enter)), Some(ControlEvent::DropBlock));
+    /// assert_eq!(bindings.get(Trigger::Button(Button::Top(3))), Some(ControlEvent::MoveRight));
+    /// assert!(Bindings::parse("[default]\nfly = Space\n", "default").is_err());
+    /// ```
+    pub fn parse(text: &str, profile: &str) -> Result<Bindings, BindingsError> {
+        Bindings::parse_onto(Bindings::default(), text, profile)
+    }
+    /// Same as parse, but on top of `base` instead of the defaults
+    fn parse_onto(base: Bindings, text: &str, profile: &str) -> Result<Bindings, BindingsError> {
+        let mut bindings = base;
+        let mut profiles: Vec<(String, Vec<Binding>)> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| BindingsError::Parse {
+                line: i + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let name = name.trim();
+                if profiles.iter().any(|(other, _)| other == name) {
+                    return Err(error(format!("[{}] is already defined", name)));
+                }
+                profiles.push((name.to_string(), Vec::new()));
+                continue;
+            }
+            let (action, triggers) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("expected `action = triggers`, got `{}`", line)))?;
+            let event = action_from_name(action.trim())
+                .ok_or_else(|| error(format!("unknown action `{}`", action.trim())))?;
+            let triggers = triggers
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(|t| {
+                    Trigger::from_name(t).ok_or_else(|| error(format!("unknown trigger `{}`", t)))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let (name, actions) = profiles
+                .last_mut()
+                .ok_or_else(|| error("bindings need to be in a [profile]".to_string()))?;
+            for trigger in &triggers {
+                let taken = actions
+                    .iter()
+                    .find(|(other, bound)| *other != event && bound.contains(trigger));
+                if let Some((other, _)) = taken {
+                    return Err(error(format!(
+                        "{:?} is already bound to {:?} in [{}]",
+                        trigger, other, name
+                    )));
+                }
+            }
+            actions.retain(|(other, _)| *other != event);
+            actions.push((event, triggers));
+        }
+
+        let find = |name: &str| profiles.iter().find(|(other, _)| other == name);
+        let mut layers = vec![find("default")];
+        if profile != "default" {
+            let layer =
+                find(profile).ok_or_else(|| BindingsError::NoProfile(profile.to_string()))?;
+            layers.push(Some(layer));
+        }
+        for (_, actions) in layers.into_iter().flatten() {
+            for (event, triggers) in actions {
+                bindings.bind(*event, triggers);
+            }
+        }
+        Ok(bindings)
+    }
+    /// Returns what the trigger does, if anything
+    pub fn get(&self, trigger: Trigger) -> Option<ControlEvent> {
+        self.map.get(&trigger).copied()
+    }
+    /// Replaces whatever triggers the event with the given triggers
+    pub fn bind(&mut self, event: ControlEvent, triggers: &[Trigger]) {
+        self.map.retain(|_, bound| *bound != event);
+        for trigger in triggers {
+            self.map.insert(*trigger, event);
+        }
+    }
+    /// Returns every trigger for the event
+    pub fn triggers(&self, event: ControlEvent) -> Vec<Trigger> {
+        self.map
+            .iter()
+            .filter(|(_, bound)| **bound == event)
+            .map(|(trigger, _)| *trigger)
+            .collect()
+    }
+}
+
+impl Trigger {
+    /// Parses a trigger as written in a bindings file
+    /// ```
+    /// # use lp_tetris::{input::{GamepadButton, Key, Trigger}, Button, Pad};
+    /// assert_eq!(Trigger::from_name("left"), Some(Trigger::Key(Key::Left)));
+    /// assert_eq!(Trigger::from_name("scene:7"), Some(Trigger::Button(Button::Scene(7))));
+    /// assert_eq!(Trigger::from_name("pad:3:4"), Some(Trigger::Button(Button::Grid(Pad { x: 3, y: 4 }))));
+    /// assert_eq!(Trigger::from_name("gamepad:south"), Some(Trigger::Gamepad(GamepadButton::South)));
+    /// assert_eq!(Trigger::from_name("top:8"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Trigger> {
+        let number = |n: &str| n.parse::<u8>().ok().filter(|n| *n < 8);
+        let parts: Vec<&str> = name.split(':').collect();
+        match parts[..] {
+            ["top", n] => Some(Trigger::Button(Button::Top(number(n)?))),
+            ["scene", n] => Some(Trigger::Button(Button::Scene(number(n)?))),
+            ["pad", x, y] => Some(Trigger::Button(Button::Grid(Pad {
+                x: number(x)?,
+                y: number(y)?,
+            }))),
+            ["gamepad", button] => GamepadButton::from_name(button).map(Trigger::Gamepad),
+            [key] => Key::from_name(key).map(Trigger::Key),
+            _ => None,
+        }
+    }
+}
+
+/// Parses an action name as written in a bindings file
+fn action_from_name(name: &str) -> Option<ControlEvent> {
+    match name {
+        "rotate_left" => Some(ControlEvent::RotateLeft),
+        "rotate_right" => Some(ControlEvent::RotateRight),
+        "move_left" => Some(ControlEvent::MoveLeft),
+        "move_right" => Some(ControlEvent::MoveRight),
+        "move_up" => Some(ControlEvent::MoveUp),
+        "move_down" => Some(ControlEvent::MoveDown),
+        "drop" => Some(ControlEvent::DropBlock),
+        "exit" => Some(ControlEvent::ExitGame),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bindings, BindingsError, Trigger, DEFAULT_BINDINGS};
+    use crate::{input::Key, Button, ControlEvent};
+
+    #[test]
+    fn defaults() {
+        let bindings = Bindings::default();
+        assert_eq!(
+            bindings.get(Trigger::Key(Key::Left)),
+            Some(ControlEvent::MoveLeft)
+        );
+        assert_eq!(bindings.get(Trigger::Key(Key::Q)), None);
+        assert_eq!(
+            bindings.get(Trigger::Button(Button::Scene(7))),
+            Some(ControlEvent::RotateRight)
+        );
+        assert_eq!(bindings.triggers(ControlEvent::ExitGame).len(), 3);
+    }
+    #[test]
+    fn errors() {
+        let line = |text: &str| match Bindings::parse(text, "default") {
+            Err(BindingsError::Parse { line, .. }) => line,
+            other => panic!("Expected a parse error, got {:?}", other),
+        };
+        assert_eq!(line("drop = Space"), 1);
+        assert_eq!(line("[default]\n\n# comment\ndrop = Spaec"), 4);
+        assert_eq!(line("[default]\ndrop = Space\nexit = Space"), 3);
+        assert_eq!(line("[default]\n[default]"), 2);
+        assert_eq!(line("[default]\ndrop Space"), 2);
+        assert!(matches!(
+            Bindings::parse("[default]\n", "player3"),
+            Err(BindingsError::NoProfile(_))
+        ));
+    }
+    #[test]
+    fn copy_of_defaults() {
+        // However it's formatted, the defaults read back as the defaults
+        let copies = [
+            format!("{}\n", DEFAULT_BINDINGS),
+            DEFAULT_BINDINGS.replace(" = ", "="),
+        ];
+        for copy in copies.iter() {
+            let bindings = Bindings::parse(copy, "default").unwrap();
+            assert_eq!(bindings.map, Bindings::default().map);
+        }
+        // And a profile that changes nothing keeps them
+        let bindings = Bindings::parse("[default]\n", "default").unwrap();
+        assert_eq!(bindings.map, Bindings::default().map);
+    }
+    #[test]
+    fn unbind() {
+        let bindings = Bindings::parse("[default]\nexit =\n", "default").unwrap();
+        assert!(bindings.triggers(ControlEvent::ExitGame).is_empty());
+        assert_eq!(
+            bindings.get(Trigger::Key(Key::Space)),
+            Some(ControlEvent::DropBlock)
+        );
+    }
+}
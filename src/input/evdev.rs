@@ -0,0 +1,302 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Reads keyboards and gamepads straight from /dev/input, Linux only.
+//! This sees key releases and works without a focused window,
+//! but the user needs permission to read the event devices.
+
+use super::{
+    gamepad::{self, Stick},
+    lookup, GamepadButton, Key, SharedBindings, Trigger,
+};
+use crate::InputEvent;
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    mem,
+    os::unix::io::AsRawFd,
+    path::PathBuf,
+    ptr,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Event type for key (and button) presses, from linux/input-event-codes.h
+const EV_KEY: u16 = 0x01;
+/// Event type for sticks and hats
+const EV_ABS: u16 = 0x03;
+// Axis codes
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+/// Returns the event devices the kernel thinks are keyboards
+pub fn keyboards() -> Vec<PathBuf> {
+    devices("kbd")
+}
+
+/// Returns the keyboards this user can actually open
+pub fn readable_keyboards() -> Vec<PathBuf> {
+    keyboards()
+        .into_iter()
+        .filter(|path| File::open(path).is_ok())
+        .collect()
+}
+
+/// Returns the event devices the kernel thinks are joysticks, which includes gamepads
+pub fn gamepads() -> Vec<PathBuf> {
+    devices("js")
+}
+
+/// Returns the event devices with the given handler
+fn devices(handler: &str) -> Vec<PathBuf> {
+    let devices = fs::read_to_string("/proc/bus/input/devices").unwrap_or_default();
+    devices
+        .split("\n\n")
+        .filter_map(|device| {
+            let handlers = device
+                .lines()
+                .find(|line| line.starts_with("H: Handlers="))?
+                .trim_start_matches("H: Handlers=");
+            if !handlers.split_whitespace().any(|h| h == handler) {
+                return None;
+            }
+            let event = handlers
+                .split_whitespace()
+                .find(|h| h.starts_with("event"))?;
+            Some(PathBuf::from("/dev/input").join(event))
+        })
+        .collect()
+}
+
+/// Opens every readable keyboard and starts a thread reading each one.
+/// Fails if none of them could be opened.
+pub fn spawn(events_tx: mpsc::Sender<InputEvent>, bindings: SharedBindings) -> io::Result<()> {
+    open_each(
+        keyboards(),
+        "No keyboards found",
+        events_tx,
+        bindings,
+        read_keyboard,
+    )
+    .map(|_| ())
+}
+
+/// Opens every readable gamepad and starts a thread reading each one.
+/// Returns how many there were, failing only if there were some but none could be opened.
+pub fn spawn_gamepads(
+    events_tx: mpsc::Sender<InputEvent>,
+    bindings: SharedBindings,
+) -> io::Result<usize> {
+    let paths = gamepads();
+    if paths.is_empty() {
+        return Ok(0);
+    }
+    open_each(
+        paths,
+        "No gamepads found",
+        events_tx,
+        bindings,
+        read_gamepad,
+    )
+}
+
+/// Opens the given devices and reads each on its own thread, returning how many opened
+fn open_each(
+    paths: Vec<PathBuf>,
+    none_found: &str,
+    events_tx: mpsc::Sender<InputEvent>,
+    bindings: SharedBindings,
+    read: fn(File, mpsc::Sender<InputEvent>, SharedBindings),
+) -> io::Result<usize> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, none_found);
+    let mut opened = 0;
+    for path in paths {
+        match File::open(&path) {
+            Ok(device) => {
+                let events_tx = events_tx.clone();
+                let bindings = bindings.clone();
+                thread::spawn(move || read(device, events_tx, bindings));
+                opened += 1;
+            }
+            Err(e) => last_err = e,
+        }
+    }
+    if opened > 0 {
+        Ok(opened)
+    } else {
+        Err(last_err)
+    }
+}
+
+/// Reads input_events from a device until it goes away or `handle` returns false.
+/// `handle` is given the type, code and value of each event, and when the kernel saw it.
+fn read_events(mut device: File, mut handle: impl FnMut(u16, u16, i32, Instant) -> bool) {
+    // struct input_event is a timeval followed by type, code and value
+    let time_len = mem::size_of::<libc::timeval>();
+    let mut buf = vec![0; time_len + 8];
+    while device.read_exact(&mut buf).is_ok() {
+        // The timestamp is wall clock time, so it's turned into how long ago the event was
+        let timeval = unsafe { ptr::read_unaligned(buf.as_ptr() as *const libc::timeval) };
+        let stamp =
+            UNIX_EPOCH + Duration::new(timeval.tv_sec as u64, timeval.tv_usec as u32 * 1000);
+        let age = SystemTime::now().duration_since(stamp).unwrap_or_default();
+        let now = Instant::now();
+        let time = now.checked_sub(age).unwrap_or(now);
+        let ev_type = u16::from_ne_bytes([buf[time_len], buf[time_len + 1]]);
+        let code = u16::from_ne_bytes([buf[time_len + 2], buf[time_len + 3]]);
+        let value = i32::from_ne_bytes([
+            buf[time_len + 4],
+            buf[time_len + 5],
+            buf[time_len + 6],
+            buf[time_len + 7],
+        ]);
+        if !handle(ev_type, code, value, time) {
+            return;
+        }
+    }
+}
+
+/// Sends the bound events for key presses and releases on a keyboard
+fn read_keyboard(device: File, events_tx: mpsc::Sender<InputEvent>, bindings: SharedBindings) {
+    read_events(device, |ev_type, code, value, time| {
+        // value is 1 for a press, 0 for a release and 2 for autorepeat,
+        // which is left to the game
+        if ev_type != EV_KEY || value == 2 {
+            return true;
+        }
+        match key_from_code(code).and_then(|key| lookup(&bindings, Trigger::Key(key), value == 1)) {
+            Some(msg) => events_tx.send(msg.at(time)).is_ok(),
+            None => true,
+        }
+    })
+}
+
+/// Sends the bound events for button presses and releases on a gamepad,
+/// with the left stick and hat acting as the D-pad
+fn read_gamepad(device: File, events_tx: mpsc::Sender<InputEvent>, bindings: SharedBindings) {
+    let x_range = axis_range(&device, ABS_X);
+    let y_range = axis_range(&device, ABS_Y);
+    let mut stick = Stick::default();
+    let mut hat = Stick::default();
+    read_events(device, |ev_type, code, value, time| {
+        let buttons = match (ev_type, code) {
+            (EV_KEY, _) if value != 2 => button_from_code(code)
+                .map(|button| (button, value == 1))
+                .into_iter()
+                .collect(),
+            (EV_ABS, ABS_X) => stick.horizontal(gamepad::normalize(value, x_range.0, x_range.1)),
+            (EV_ABS, ABS_Y) => stick.vertical(gamepad::normalize(value, y_range.0, y_range.1)),
+            (EV_ABS, ABS_HAT0X) => hat.horizontal(value as f64),
+            (EV_ABS, ABS_HAT0Y) => hat.vertical(value as f64),
+            _ => Vec::new(),
+        };
+        buttons
+            .into_iter()
+            .filter_map(|(button, pressed)| lookup(&bindings, Trigger::Gamepad(button), pressed))
+            .all(|msg| events_tx.send(msg.at(time)).is_ok())
+    })
+}
+
+/// Asks the kernel for the minimum and maximum of an axis, guessing a signed 16 bit
+/// axis if it won't say
+fn axis_range(device: &File, axis: u16) -> (i32, i32) {
+    // struct input_absinfo: value, minimum, maximum, fuzz, flat, resolution
+    let mut info = [0i32; 6];
+    // EVIOCGABS(axis), i.e. _IOR('E', 0x40 + axis, struct input_absinfo)
+    let request =
+        (2 << 30) | (mem::size_of_val(&info) << 16) | (0x45 << 8) | (0x40 + axis as usize);
+    let result = unsafe { libc::ioctl(device.as_raw_fd(), request as _, info.as_mut_ptr()) };
+    if result < 0 || info[2] <= info[1] {
+        (-32768, 32767)
+    } else {
+        (info[1], info[2])
+    }
+}
+
+/// Converts a BTN_* code from linux/input-event-codes.h into a GamepadButton
+pub fn button_from_code(code: u16) -> Option<GamepadButton> {
+    match code {
+        0x130 => Some(GamepadButton::South),
+        0x131 => Some(GamepadButton::East),
+        0x133 => Some(GamepadButton::North),
+        0x134 => Some(GamepadButton::West),
+        0x136 => Some(GamepadButton::LeftShoulder),
+        0x137 => Some(GamepadButton::RightShoulder),
+        0x13a => Some(GamepadButton::Select),
+        0x13b => Some(GamepadButton::Start),
+        0x220 => Some(GamepadButton::DPadUp),
+        0x221 => Some(GamepadButton::DPadDown),
+        0x222 => Some(GamepadButton::DPadLeft),
+        0x223 => Some(GamepadButton::DPadRight),
+        _ => None,
+    }
+}
+
+/// Converts a KEY_* code from linux/input-event-codes.h into a Key
+pub fn key_from_code(code: u16) -> Option<Key> {
+    match code {
+        1 => Some(Key::Escape),
+        2 => Some(Key::One),
+        3 => Some(Key::Two),
+        4 => Some(Key::Three),
+        5 => Some(Key::Four),
+        6 => Some(Key::Five),
+        7 => Some(Key::Six),
+        8 => Some(Key::Seven),
+        9 => Some(Key::Eight),
+        10 => Some(Key::Nine),
+        11 => Some(Key::Zero),
+        14 => Some(Key::Backspace),
+        15 => Some(Key::Tab),
+        16 => Some(Key::Q),
+        17 => Some(Key::W),
+        18 => Some(Key::E),
+        19 => Some(Key::R),
+        20 => Some(Key::T),
+        21 => Some(Key::Y),
+        22 => Some(Key::U),
+        23 => Some(Key::I),
+        24 => Some(Key::O),
+        25 => Some(Key::P),
+        28 => Some(Key::Enter),
+        30 => Some(Key::A),
+        31 => Some(Key::S),
+        32 => Some(Key::D),
+        33 => Some(Key::F),
+        34 => Some(Key::G),
+        35 => Some(Key::H),
+        36 => Some(Key::J),
+        37 => Some(Key::K),
+        38 => Some(Key::L),
+        44 => Some(Key::Z),
+        45 => Some(Key::X),
+        46 => Some(Key::C),
+        47 => Some(Key::V),
+        48 => Some(Key::B),
+        49 => Some(Key::N),
+        50 => Some(Key::M),
+        57 => Some(Key::Space),
+        103 => Some(Key::Up),
+        105 => Some(Key::Left),
+        106 => Some(Key::Right),
+        108 => Some(Key::Down),
+        _ => None,
+    }
+}
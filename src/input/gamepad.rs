@@ -0,0 +1,151 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! The platform independent half of gamepad input.
+//! Backends report buttons as GamepadButtons and sticks and hats as axis values,
+//! and the left stick and hat both work as the D-pad.
+
+use super::GamepadButton;
+
+/// How far a stick has to be pushed, out of 1, before it counts as a D-pad press.
+/// Worn sticks rest a little off centre, so this is fairly generous.
+pub const DEADZONE: f64 = 0.4;
+
+/// Turns an analog stick (or a hat, which is a stick that's only ever -1, 0 or 1)
+/// into D-pad presses.
+/// A direction is pressed when the stick goes past the deadzone,
+/// and released when it comes back inside it or goes the other way.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stick {
+    deadzone: f64,
+    x: Option<GamepadButton>,
+    y: Option<GamepadButton>,
+}
+
+impl Stick {
+    pub fn new(deadzone: f64) -> Stick {
+        Stick {
+            deadzone,
+            x: None,
+            y: None,
+        }
+    }
+    /// Updates the horizontal axis, from -1 (left) to 1 (right).
+    /// Returns the directions that were released and pressed, in that order.
+    /// ```
+    /// # use lp_tetris::input::{gamepad::Stick, GamepadButton};
+    /// let mut stick = Stick::new(0.5);
+    /// assert_eq!(stick.horizontal(0.2), vec![]);
+    /// assert_eq!(stick.horizontal(-0.9), vec![(GamepadButton::DPadLeft, true)]);
+    /// assert_eq!(stick.horizontal(-1.0), vec![]);
+    /// assert_eq!(stick.horizontal(0.0), vec![(GamepadButton::DPadLeft, false)]);
+    /// ```
+    pub fn horizontal(&mut self, value: f64) -> Vec<(GamepadButton, bool)> {
+        let direction = self.direction(value, GamepadButton::DPadLeft, GamepadButton::DPadRight);
+        moved(&mut self.x, direction)
+    }
+    /// Updates the vertical axis, from -1 (up) to 1 (down).
+    /// Returns the directions that were released and pressed, in that order.
+    pub fn vertical(&mut self, value: f64) -> Vec<(GamepadButton, bool)> {
+        let direction = self.direction(value, GamepadButton::DPadUp, GamepadButton::DPadDown);
+        moved(&mut self.y, direction)
+    }
+    /// Returns which way an axis is pushed, if it's outside the deadzone
+    fn direction(
+        &self,
+        value: f64,
+        negative: GamepadButton,
+        positive: GamepadButton,
+    ) -> Option<GamepadButton> {
+        if value <= -self.deadzone {
+            Some(negative)
+        } else if value >= self.deadzone {
+            Some(positive)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Stick {
+    fn default() -> Stick {
+        Stick::new(DEADZONE)
+    }
+}
+
+/// Records where an axis is pushed, returning the release of the old direction
+/// and the press of the new one if it changed
+fn moved(
+    held: &mut Option<GamepadButton>,
+    direction: Option<GamepadButton>,
+) -> Vec<(GamepadButton, bool)> {
+    if *held == direction {
+        return Vec::new();
+    }
+    let released = held.map(|button| (button, false));
+    let pressed = direction.map(|button| (button, true));
+    *held = direction;
+    released.into_iter().chain(pressed).collect()
+}
+
+/// Scales a raw axis reading between min and max to -1..1
+pub fn normalize(value: i32, min: i32, max: i32) -> f64 {
+    if max <= min {
+        return 0.0;
+    }
+    let value = value.clamp(min, max);
+    2.0 * (value - min) as f64 / (max - min) as f64 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, Stick};
+    use crate::input::GamepadButton;
+
+    #[test]
+    fn straight_across() {
+        // Going from one side to the other without stopping in the middle
+        let mut stick = Stick::new(0.5);
+        assert_eq!(
+            stick.horizontal(1.0),
+            vec![(GamepadButton::DPadRight, true)]
+        );
+        assert_eq!(
+            stick.horizontal(-1.0),
+            vec![
+                (GamepadButton::DPadRight, false),
+                (GamepadButton::DPadLeft, true)
+            ]
+        );
+        assert_eq!(stick.vertical(1.0), vec![(GamepadButton::DPadDown, true)]);
+        assert_eq!(stick.horizontal(-1.0), vec![]);
+    }
+    #[test]
+    fn hat() {
+        let mut hat = Stick::new(0.5);
+        assert_eq!(hat.vertical(-1.0), vec![(GamepadButton::DPadUp, true)]);
+        assert_eq!(hat.vertical(0.0), vec![(GamepadButton::DPadUp, false)]);
+        assert_eq!(hat.vertical(-1.0), vec![(GamepadButton::DPadUp, true)]);
+    }
+    #[test]
+    fn ranges() {
+        assert_eq!(normalize(0, 0, 255), -1.0);
+        assert_eq!(normalize(255, 0, 255), 1.0);
+        assert!(normalize(0, -32768, 32767).abs() < 0.01);
+        assert_eq!(normalize(5, 0, 0), 0.0);
+    }
+}
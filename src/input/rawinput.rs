@@ -0,0 +1,166 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Windows raw input, via multiinput.
+//! This reads gamepads as well as keyboards.
+
+use super::{gamepad::Stick, lookup, GamepadButton, Key, SharedBindings, Trigger};
+use crate::InputEvent;
+use multiinput::*;
+use std::{collections::HashMap, io, sync::mpsc, thread};
+
+/// Registers all keyboards and gamepads and starts a thread polling them
+pub fn spawn(events_tx: mpsc::Sender<InputEvent>, bindings: SharedBindings) -> io::Result<()> {
+    let mut manager = RawInputManager::new().map_err(io::Error::other)?;
+    manager.register_devices(DeviceType::Keyboards);
+    manager.register_devices(DeviceType::Joysticks(XInputInclude::True));
+    let mut sticks = Sticks::default();
+    thread::spawn(move || loop {
+        if let Some(event) = manager.get_event() {
+            for msg in input_map(event, &mut sticks, &bindings) {
+                if events_tx.send(msg).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Where each gamepad's left stick and hat are pushed, by device id
+#[derive(Default)]
+pub struct Sticks(HashMap<usize, (Stick, Stick)>);
+
+/// Given a multiinput::RawEvent, return the InputEvents it causes.
+/// Mappings live in the Bindings, this just handles the conversion.
+/// Gamepad sticks and hats act as the D-pad.
+pub fn input_map(
+    event: RawEvent,
+    sticks: &mut Sticks,
+    bindings: &SharedBindings,
+) -> Vec<InputEvent> {
+    let buttons = match event {
+        RawEvent::KeyboardEvent(_, id, state) => {
+            return key_from_id(id)
+                .and_then(|key| lookup(bindings, Trigger::Key(key), state == State::Pressed))
+                .into_iter()
+                .collect();
+        }
+        RawEvent::JoystickButtonEvent(_, index, state) => button_from_index(index)
+            .map(|button| (button, state == State::Pressed))
+            .into_iter()
+            .collect(),
+        RawEvent::JoystickAxisEvent(id, axis, value) => {
+            let (stick, _) = sticks.0.entry(id).or_default();
+            match axis {
+                Axis::X => stick.horizontal(value),
+                // multiinput has up as positive
+                Axis::Y => stick.vertical(-value),
+                _ => Vec::new(),
+            }
+        }
+        RawEvent::JoystickHatSwitchEvent(id, position) => {
+            let (_, hat) = sticks.0.entry(id).or_default();
+            let (x, y) = match position {
+                HatSwitch::Center => (0.0, 0.0),
+                HatSwitch::Up => (0.0, -1.0),
+                HatSwitch::UpRight => (1.0, -1.0),
+                HatSwitch::Right => (1.0, 0.0),
+                HatSwitch::DownRight => (1.0, 1.0),
+                HatSwitch::Down => (0.0, 1.0),
+                HatSwitch::DownLeft => (-1.0, 1.0),
+                HatSwitch::Left => (-1.0, 0.0),
+                HatSwitch::UpLeft => (-1.0, -1.0),
+            };
+            let mut buttons = hat.horizontal(x);
+            buttons.extend(hat.vertical(y));
+            buttons
+        }
+        _ => Vec::new(),
+    };
+    buttons
+        .into_iter()
+        .filter_map(|(button, pressed)| lookup(bindings, Trigger::Gamepad(button), pressed))
+        .collect()
+}
+
+/// Converts a joystick button number into a GamepadButton, using the xbox layout
+pub fn button_from_index(index: usize) -> Option<GamepadButton> {
+    match index {
+        0 => Some(GamepadButton::South),
+        1 => Some(GamepadButton::East),
+        2 => Some(GamepadButton::West),
+        3 => Some(GamepadButton::North),
+        4 => Some(GamepadButton::LeftShoulder),
+        5 => Some(GamepadButton::RightShoulder),
+        6 => Some(GamepadButton::Select),
+        7 => Some(GamepadButton::Start),
+        _ => None,
+    }
+}
+
+/// Converts a multiinput::KeyId into a Key
+pub fn key_from_id(id: KeyId) -> Option<Key> {
+    match id {
+        KeyId::A => Some(Key::A),
+        KeyId::B => Some(Key::B),
+        KeyId::C => Some(Key::C),
+        KeyId::D => Some(Key::D),
+        KeyId::E => Some(Key::E),
+        KeyId::F => Some(Key::F),
+        KeyId::G => Some(Key::G),
+        KeyId::H => Some(Key::H),
+        KeyId::I => Some(Key::I),
+        KeyId::J => Some(Key::J),
+        KeyId::K => Some(Key::K),
+        KeyId::L => Some(Key::L),
+        KeyId::M => Some(Key::M),
+        KeyId::N => Some(Key::N),
+        KeyId::O => Some(Key::O),
+        KeyId::P => Some(Key::P),
+        KeyId::Q => Some(Key::Q),
+        KeyId::R => Some(Key::R),
+        KeyId::S => Some(Key::S),
+        KeyId::T => Some(Key::T),
+        KeyId::U => Some(Key::U),
+        KeyId::V => Some(Key::V),
+        KeyId::W => Some(Key::W),
+        KeyId::X => Some(Key::X),
+        KeyId::Y => Some(Key::Y),
+        KeyId::Z => Some(Key::Z),
+        KeyId::Zero => Some(Key::Zero),
+        KeyId::One => Some(Key::One),
+        KeyId::Two => Some(Key::Two),
+        KeyId::Three => Some(Key::Three),
+        KeyId::Four => Some(Key::Four),
+        KeyId::Five => Some(Key::Five),
+        KeyId::Six => Some(Key::Six),
+        KeyId::Seven => Some(Key::Seven),
+        KeyId::Eight => Some(Key::Eight),
+        KeyId::Nine => Some(Key::Nine),
+        KeyId::Left => Some(Key::Left),
+        KeyId::Right => Some(Key::Right),
+        KeyId::Up => Some(Key::Up),
+        KeyId::Down => Some(Key::Down),
+        KeyId::Space => Some(Key::Space),
+        KeyId::Return => Some(Key::Enter),
+        KeyId::Escape => Some(Key::Escape),
+        KeyId::Backspace => Some(Key::Backspace),
+        KeyId::Tab => Some(Key::Tab),
+        _ => None,
+    }
+}
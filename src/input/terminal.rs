@@ -0,0 +1,260 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Reads keys from the controlling terminal.
+//! Terminals only report presses (with the OS's own key repeat),
+//! so this is the fallback for when nothing better is available, e.g. over SSH.
+//! Every key is released straight after it's pressed, so keys can't be held.
+
+use super::{lookup, Key, SharedBindings, Trigger};
+use crate::{ControlEvent, InputEvent};
+use std::{
+    io::{self, Read},
+    mem,
+    sync::mpsc,
+    thread,
+};
+
+/// Returns whether stdin is a terminal
+pub fn is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+/// Puts the terminal into raw mode until dropped.
+/// Signals are turned off too, so ctrl-c arrives as a byte instead of killing
+/// the game before it can give the terminal back.
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<RawMode> {
+        unsafe {
+            let mut original: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/// Switches the terminal to raw mode and starts a thread reading stdin
+pub fn spawn(events_tx: mpsc::Sender<InputEvent>, bindings: SharedBindings) -> io::Result<RawMode> {
+    if !is_tty() {
+        return Err(io::Error::other("stdin is not a terminal"));
+    }
+    let raw_mode = RawMode::enable()?;
+    thread::spawn(move || {
+        let mut parser = Parser::default();
+        for byte in io::stdin().lock().bytes() {
+            let byte = match byte {
+                Ok(byte) => byte,
+                Err(_) => return,
+            };
+            if events(&mut parser, byte, &bindings)
+                .into_iter()
+                .any(|msg| events_tx.send(msg).is_err())
+            {
+                return;
+            }
+        }
+    });
+    Ok(raw_mode)
+}
+
+/// The byte ctrl-c sends when signals are off
+const INTERRUPT: u8 = 0x03;
+
+/// Turns the next byte from the terminal into a press and release.
+/// Ctrl-c always exits the game, whatever the bindings say.
+fn events(parser: &mut Parser, byte: u8, bindings: &SharedBindings) -> Vec<InputEvent> {
+    if byte == INTERRUPT {
+        return vec![
+            InputEvent::press(ControlEvent::ExitGame),
+            InputEvent::release(ControlEvent::ExitGame),
+        ];
+    }
+    match parser.feed(byte) {
+        Some(key) => lookup(bindings, Trigger::Key(key), true)
+            .into_iter()
+            .chain(lookup(bindings, Trigger::Key(key), false))
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Turns a stream of terminal bytes into keys, handling arrow key escape sequences.
+/// A lone escape can't be told apart from the start of a sequence,
+/// so Escape is only reported when it's pressed twice.
+#[derive(Default, Debug)]
+pub struct Parser {
+    escape: Vec<u8>,
+}
+
+impl Parser {
+    /// Feeds the next byte in, returning a key if one has been completed
+    pub fn feed(&mut self, byte: u8) -> Option<Key> {
+        if !self.escape.is_empty() {
+            self.escape.push(byte);
+            return match &self.escape[..] {
+                [0x1B, 0x1B] => self.finish(Some(Key::Escape)),
+                [0x1B, b'['] | [0x1B, b'O'] => None,
+                [0x1B, b'[', b'A'] | [0x1B, b'O', b'A'] => self.finish(Some(Key::Up)),
+                [0x1B, b'[', b'B'] | [0x1B, b'O', b'B'] => self.finish(Some(Key::Down)),
+                [0x1B, b'[', b'C'] | [0x1B, b'O', b'C'] => self.finish(Some(Key::Right)),
+                [0x1B, b'[', b'D'] | [0x1B, b'O', b'D'] => self.finish(Some(Key::Left)),
+                // Some other sequence, skip to its final byte
+                [0x1B, b'O', _] => self.finish(None),
+                [0x1B, b'[', .., last] if (0x40..=0x7E).contains(last) => self.finish(None),
+                [0x1B, b'[', ..] => None,
+                // Alt plus a key, treat it as just the key
+                _ => {
+                    self.escape.clear();
+                    key_from_byte(byte)
+                }
+            };
+        }
+        if byte == 0x1B {
+            self.escape.push(byte);
+            return None;
+        }
+        key_from_byte(byte)
+    }
+    fn finish(&mut self, key: Option<Key>) -> Option<Key> {
+        self.escape.clear();
+        key
+    }
+}
+
+/// Converts a single byte of terminal input into a Key
+pub fn key_from_byte(byte: u8) -> Option<Key> {
+    match byte.to_ascii_lowercase() {
+        b'a' => Some(Key::A),
+        b'b' => Some(Key::B),
+        b'c' => Some(Key::C),
+        b'd' => Some(Key::D),
+        b'e' => Some(Key::E),
+        b'f' => Some(Key::F),
+        b'g' => Some(Key::G),
+        b'h' => Some(Key::H),
+        b'i' => Some(Key::I),
+        b'j' => Some(Key::J),
+        b'k' => Some(Key::K),
+        b'l' => Some(Key::L),
+        b'm' => Some(Key::M),
+        b'n' => Some(Key::N),
+        b'o' => Some(Key::O),
+        b'p' => Some(Key::P),
+        b'q' => Some(Key::Q),
+        b'r' => Some(Key::R),
+        b's' => Some(Key::S),
+        b't' => Some(Key::T),
+        b'u' => Some(Key::U),
+        b'v' => Some(Key::V),
+        b'w' => Some(Key::W),
+        b'x' => Some(Key::X),
+        b'y' => Some(Key::Y),
+        b'z' => Some(Key::Z),
+        b'0' => Some(Key::Zero),
+        b'1' => Some(Key::One),
+        b'2' => Some(Key::Two),
+        b'3' => Some(Key::Three),
+        b'4' => Some(Key::Four),
+        b'5' => Some(Key::Five),
+        b'6' => Some(Key::Six),
+        b'7' => Some(Key::Seven),
+        b'8' => Some(Key::Eight),
+        b'9' => Some(Key::Nine),
+        b' ' => Some(Key::Space),
+        b'\r' | b'\n' => Some(Key::Enter),
+        b'\t' => Some(Key::Tab),
+        0x7F | 0x08 => Some(Key::Backspace),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{events, Key, Parser};
+    use crate::{input::Bindings, ControlEvent};
+    use std::sync::{Arc, RwLock};
+
+    fn parse(bytes: &[u8]) -> Vec<Key> {
+        let mut parser = Parser::default();
+        bytes.iter().filter_map(|b| parser.feed(*b)).collect()
+    }
+    #[test]
+    fn plain_keys() {
+        assert_eq!(
+            parse(b"aD 1\x7f"),
+            vec![Key::A, Key::D, Key::Space, Key::One, Key::Backspace]
+        );
+    }
+    #[test]
+    fn arrows() {
+        assert_eq!(
+            parse(b"\x1b[A\x1b[B\x1b[C\x1b[D\x1bOD"),
+            vec![Key::Up, Key::Down, Key::Right, Key::Left, Key::Left]
+        );
+    }
+    #[test]
+    fn other_sequences() {
+        // F5, then delete, then alt-a, then escape
+        assert_eq!(
+            parse(b"\x1b[15~\x1b[3~a\x1ba\x1b\x1b"),
+            vec![Key::A, Key::A, Key::Escape]
+        );
+    }
+    #[test]
+    fn function_keys() {
+        // F1 to F4, then F1 as some terminals send it
+        assert_eq!(parse(b"\x1bOP\x1bOQ\x1bOR\x1bOSa\x1b[11~"), vec![Key::A]);
+    }
+    #[test]
+    fn ctrl_c_exits() {
+        let bindings = Arc::new(RwLock::new(Bindings::empty()));
+        let mut parser = Parser::default();
+        let sent: Vec<_> = events(&mut parser, 0x03, &bindings)
+            .into_iter()
+            .map(|e| (e.event, e.pressed))
+            .collect();
+        assert_eq!(
+            sent,
+            vec![
+                (ControlEvent::ExitGame, true),
+                (ControlEvent::ExitGame, false)
+            ]
+        );
+        assert!(events(&mut parser, b'a', &bindings).is_empty());
+    }
+}
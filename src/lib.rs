@@ -15,14 +15,101 @@
  * --------------------
  */
 
-use multiinput::*;
-use std::{sync::mpsc, thread};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+mod ansi;
+mod color;
+mod display;
+mod error;
+mod frame;
+pub mod input;
+pub mod mock;
+mod model;
+mod multi;
+pub mod palette;
+pub mod record;
+mod repeat;
+pub mod viewport;
+pub use ansi::TerminalDisplay;
+pub use color::Color;
+pub use display::{Controller, GridDisplay, Status};
+pub use error::LaunchpadError;
+pub use frame::FrameBuffer;
+pub use model::LaunchpadModel;
+pub use multi::MultiLaunchpad;
+pub use repeat::AutoRepeat;
 
 pub struct Launchpad {
-    conn_out: midir::MidiOutputConnection,
-    events_rx: mpsc::Receiver<ControlEvent>,
+    model: LaunchpadModel,
+    conn_out: Box<dyn MidiSink>,
+    conn_in: Option<midir::MidiInputConnection<()>>,
+    events_rx: mpsc::Receiver<InputEvent>,
+    _keyboard: Option<input::InputHandle>,
+    /// Set while text is scrolling, cleared by the input callback when the device says it's done
+    scrolling: Arc<AtomicBool>,
+    /// When text is expected to finish, for models that don't say
+    scroll_until: Option<Instant>,
+    /// What's currently on the grid, so send_matrix only sends changes
+    frame: FrameBuffer,
+    /// For sending Disconnected and Reconnected alongside the input events
+    events_tx: mpsc::Sender<InputEvent>,
+    /// What the buttons (and keyboard) do, shared with the input threads
+    bindings: input::SharedBindings,
+    /// Where to record MIDI traffic, shared with the input callback
+    recorder: Arc<Mutex<Option<record::Recorder>>>,
+    /// Where the launchpad was opened, so it can be found again. None for with_output
+    device: Option<Device>,
+    /// Whether to wait for the device to come back when it goes away, instead of failing sends
+    reconnect: bool,
+    connected: bool,
+    /// When poll_input should next check the device is there, or try to reconnect
+    next_check: Instant,
+}
+/// How often to check the launchpad is still plugged in, or try to reconnect when it isn't
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+/// The port a launchpad was opened on
+struct Device {
+    port: PortInfo,
+    /// Kept around to list ports without making a new client every check
+    watcher: midir::MidiOutput,
+}
+/// Somewhere raw MIDI messages can be written to.
+/// This is a midir connection when talking to real hardware,
+/// or a mock::MockOutput when testing.
+pub trait MidiSink {
+    /// Writes a single complete MIDI message
+    fn send(&mut self, msg: &[u8]) -> Result<(), LaunchpadError>;
+}
+/// Sends raw messages to the launchpad, e.g. from a recording.
+/// They could change anything, so the next frame is sent in full.
+/// Failures are handled the same as for send_sysex.
+impl MidiSink for Launchpad {
+    fn send(&mut self, msg: &[u8]) -> Result<(), LaunchpadError> {
+        self.frame.invalidate();
+        if !self.connected {
+            return Ok(());
+        }
+        let result = self.write_raw(msg);
+        if result.is_err() && self.reconnect {
+            self.disconnect();
+            return Ok(());
+        }
+        result
+    }
 }
-#[derive(Debug, PartialEq)]
+impl MidiSink for midir::MidiOutputConnection {
+    fn send(&mut self, msg: &[u8]) -> Result<(), LaunchpadError> {
+        midir::MidiOutputConnection::send(self, msg)
+            .map_err(|e| LaunchpadError::SendFailed(e.to_string()))
+    }
+}
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ControlEvent {
     RotateRight,
     RotateLeft,
@@ -33,10 +120,45 @@ pub enum ControlEvent {
     DropBlock,
     SpeedChange(u8),
     ExitGame,
+    /// The display went away, anything drawn until Reconnected is dropped
+    Disconnected,
+    /// The display is back and showing the last frame again
+    Reconnected,
+}
+/// A ControlEvent, whether the key or button behind it was pressed or released, and when.
+/// Events that don't come from a key, like Disconnected, are always presses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub event: ControlEvent,
+    pub pressed: bool,
+    /// When the input was read, as near to the hardware as the backend can tell
+    pub time: Instant,
+}
+impl InputEvent {
+    /// A press, happening now
+    pub fn press(event: ControlEvent) -> InputEvent {
+        InputEvent {
+            event,
+            pressed: true,
+            time: Instant::now(),
+        }
+    }
+    /// A release, happening now
+    pub fn release(event: ControlEvent) -> InputEvent {
+        InputEvent {
+            event,
+            pressed: false,
+            time: Instant::now(),
+        }
+    }
+    /// Returns the same event happening at a different time
+    pub fn at(self, time: Instant) -> InputEvent {
+        InputEvent { time, ..self }
+    }
 }
 /// Represents a pad on the Launchpad
 /// Provides methods to convert to and from a note byte
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Pad {
     pub x: u8,
     pub y: u8,
@@ -89,44 +211,289 @@ impl Pad {
         Pad { x, y }
     }
 }
+/// Any of the launchpad's buttons: a grid pad, or one of the round buttons around it
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Button {
+    /// One of the 8x8 grid
+    Grid(Pad),
+    /// One of the round buttons along the top, 0 (left) to 7 (right)
+    Top(u8),
+    /// One of the round buttons down the right, 0 (bottom) to 7 (top) like Pad.y
+    Scene(u8),
+}
+
+// Button impl
+impl Button {
+    /// Returns the number the given model uses for this button,
+    /// both as its LED index and in the MIDI messages it sends.
+    /// None if the button isn't on a launchpad, i.e. Top(8) or a pad past the grid.
+    /// ```
+    /// # use lp_tetris::{Button, LaunchpadModel, Pad};
+    /// assert_eq!(Button::Grid(Pad { x: 1, y: 0 }).index(LaunchpadModel::Mk2), Some(12));
+    /// assert_eq!(Button::Top(0).index(LaunchpadModel::Mk2), Some(104));
+    /// assert_eq!(Button::Top(0).index(LaunchpadModel::X), Some(91));
+    /// assert_eq!(Button::Scene(7).index(LaunchpadModel::X), Some(89));
+    /// assert_eq!(Button::Scene(30).index(LaunchpadModel::Mk2), None);
+    /// assert_eq!(Button::Top(255).index(LaunchpadModel::Mk2), None);
+    /// assert_eq!(Button::Grid(Pad { x: 8, y: 0 }).index(LaunchpadModel::Mk2), None);
+    /// ```
+    pub fn index(&self, model: LaunchpadModel) -> Option<u8> {
+        match *self {
+            Button::Grid(pad) if pad.x < 8 && pad.y < 8 => Some(pad.note()),
+            Button::Top(n) if n < 8 => Some(model.top_row_cc() + n),
+            Button::Scene(n) if n < 8 => Some(10 * (n + 1) + 9),
+            _ => None,
+        }
+    }
+    /// Returns the button with the given index on the given model, if there is one.
+    /// This is the inverse of .index
+    /// ```
+    /// # use lp_tetris::{Button, LaunchpadModel};
+    /// for model in LaunchpadModel::ALL.iter() {
+    ///     let button = Button::Top(5);
+    ///     assert_eq!(Button::from_index(*model, button.index(*model).unwrap()), Some(button));
+    /// }
+    /// assert_eq!(Button::from_index(LaunchpadModel::Mk2, 99), None);
+    /// ```
+    pub fn from_index(model: LaunchpadModel, index: u8) -> Option<Button> {
+        let (tens, ones) = (index / 10, index % 10);
+        match (tens, ones) {
+            (1..=8, 1..=8) => Some(Button::Grid(Pad::from_note(index))),
+            (1..=8, 9) => Some(Button::Scene(tens - 1)),
+            _ => match index.checked_sub(model.top_row_cc()) {
+                Some(n) if n < 8 => Some(Button::Top(n)),
+                _ => None,
+            },
+        }
+    }
+    /// Decodes a raw MIDI message from the given model into the button it's about,
+    /// and whether it was pressed (true) or released (false).
+    /// Returns None for anything that isn't a button.
+    /// ```
+    /// # use lp_tetris::{Button, LaunchpadModel, Pad};
+    /// let model = LaunchpadModel::Mk2;
+    /// assert_eq!(Button::from_midi(model, &[0xB0, 104, 127]), Some((Button::Top(0), true)));
+    /// assert_eq!(Button::from_midi(model, &[0x90, 19, 0]), Some((Button::Scene(0), false)));
+    /// assert_eq!(Button::from_midi(model, &[0x90, 11, 90]), Some((Button::Grid(Pad { x: 0, y: 0 }), true)));
+    /// assert_eq!(Button::from_midi(LaunchpadModel::X, &[0xB0, 19, 127]), Some((Button::Scene(0), true)));
+    /// assert_eq!(Button::from_midi(LaunchpadModel::X, &[0x90, 19, 127]), None);
+    /// ```
+    pub fn from_midi(model: LaunchpadModel, msg: &[u8]) -> Option<(Button, bool)> {
+        let (status, index, value) = match msg {
+            [status, index, value] => (*status, *index, *value),
+            _ => return None,
+        };
+        let button = Button::from_index(model, index)?;
+        // Scene buttons are control changes on everything but the MK2
+        let is_note = match button {
+            Button::Grid(_) => true,
+            Button::Top(_) => false,
+            Button::Scene(_) => model.scene_is_note(),
+        };
+        match status {
+            0x90 if is_note => Some((button, value > 0)),
+            0x80 if is_note => Some((button, false)),
+            0xB0 if !is_note => Some((button, value > 0)),
+            _ => None,
+        }
+    }
+}
+/// A MIDI output port, as listed by Launchpad::list_ports
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortInfo {
+    /// What to pass to Launchpad::open_by_index
+    pub index: usize,
+    /// What to pass to Launchpad::open_by_name
+    pub name: String,
+    /// Which launchpad is on this port, or None if it isn't one
+    pub model: Option<LaunchpadModel>,
+}
 // Core defs
 impl Launchpad {
-    /// Finds a launchpad and returns it.
-    /// Handles finding the port as well as connecting to it.
-    /// Panics if a launchpad is not found.
-    pub fn new() -> Launchpad {
-        // Find output port
-        let midi_out = midir::MidiOutput::new("Launchpad MK2").unwrap();
-        let mut out_port: Option<usize> = None;
-        for i in 0..midi_out.port_count() {
-            if midi_out.port_name(i).unwrap().contains("Launchpad MK2") {
-                out_port = Some(i);
-            }
-        }
-        let out_port = out_port.expect("Couldn't find launchpad!");
-
-        let conn_out = midi_out
-            .connect(out_port, "")
-            .expect("Failed to open connection");
-        // let (c_tx, c_rx) = mpsc::channel();
+    /// Lists every MIDI output port, launchpad or not
+    pub fn list_ports() -> Result<Vec<PortInfo>, LaunchpadError> {
+        let midi_out = midir::MidiOutput::new("lp_tetris")
+            .map_err(|e| LaunchpadError::MidiInit(e.to_string()))?;
+        Ok(output_ports(&midi_out))
+    }
+    /// Finds a launchpad of any supported model and connects to it.
+    /// Picks the first one if there's more than one.
+    /// See open_port for what else this does.
+    pub fn open() -> Result<Launchpad, LaunchpadError> {
+        let port = Launchpad::list_ports()?
+            .into_iter()
+            .find(|port| port.model.is_some())
+            .ok_or(LaunchpadError::NoDevice)?;
+        Launchpad::open_port(&port)
+    }
+    /// Connects to the launchpad on the output port with exactly this name
+    pub fn open_by_name(name: &str) -> Result<Launchpad, LaunchpadError> {
+        let port = Launchpad::list_ports()?
+            .into_iter()
+            .find(|port| port.name == name)
+            .ok_or(LaunchpadError::NoDevice)?;
+        Launchpad::open_port(&port)
+    }
+    /// Connects to the launchpad on the output port with this index in list_ports
+    pub fn open_by_index(index: usize) -> Result<Launchpad, LaunchpadError> {
+        let port = Launchpad::list_ports()?
+            .into_iter()
+            .find(|port| port.index == index)
+            .ok_or(LaunchpadError::NoDevice)?;
+        Launchpad::open_port(&port)
+    }
+    /// Connects to the launchpad on the given output port and its matching input port,
+    /// starts reading the keyboard and any gamepads, and puts the device into programmer mode.
+    /// Fails with NoDevice if the port isn't a supported launchpad.
+    ///
+    /// If the launchpad is unplugged later on, see set_reconnect.
+    pub fn open_port(port: &PortInfo) -> Result<Launchpad, LaunchpadError> {
+        Launchpad::open_port_with(port, true)
+    }
+    /// Same as open_port, but the keyboard and gamepads are only read if `read_keyboard` is set,
+    /// so that several launchpads don't all read the same keys
+    pub(crate) fn open_port_with(
+        port: &PortInfo,
+        read_keyboard: bool,
+    ) -> Result<Launchpad, LaunchpadError> {
+        let model = port.model.ok_or(LaunchpadError::NoDevice)?;
+        let watcher = midir::MidiOutput::new("lp_tetris")
+            .map_err(|e| LaunchpadError::MidiInit(e.to_string()))?;
         let (events_tx, events_rx) = mpsc::channel();
-        let mut manager = RawInputManager::new().unwrap();
-        manager.register_devices(DeviceType::Keyboards);
-        thread::spawn(move || loop {
-            if let Some(event) = manager.get_event() {
-                if let Some(msg) = input_map(event) {
-                    events_tx.send(msg).ok();
+        let scrolling = Arc::new(AtomicBool::new(false));
+        let bindings = input::SharedBindings::default();
+        let recorder = Arc::new(Mutex::new(None));
+        let (conn_out, conn_in) = connect(
+            port,
+            model,
+            events_tx.clone(),
+            scrolling.clone(),
+            bindings.clone(),
+            recorder.clone(),
+        )?;
+
+        // Keyboard input is optional, the pads work without it
+        let keyboard = if !read_keyboard {
+            None
+        } else {
+            match input::spawn_detected(events_tx.clone(), bindings.clone()) {
+                Ok((_, handle)) => Some(handle),
+                Err(e) => {
+                    eprintln!("Couldn't read keyboard input: {}", e);
+                    None
                 }
             }
-        });
+        };
+        if read_keyboard {
+            if let Err(e) = input::spawn_gamepads(events_tx.clone(), bindings.clone()) {
+                eprintln!("Couldn't read gamepads: {}", e);
+            }
+        }
+        let mut lp = Launchpad {
+            model,
+            conn_out: Box::new(conn_out),
+            conn_in: Some(conn_in),
+            events_rx,
+            _keyboard: keyboard,
+            scrolling,
+            scroll_until: None,
+            frame: FrameBuffer::new(),
+            events_tx,
+            bindings,
+            recorder,
+            device: Some(Device {
+                port: port.clone(),
+                watcher,
+            }),
+            reconnect: true,
+            connected: true,
+            next_check: Instant::now() + CHECK_INTERVAL,
+        };
+        for (msg_type, data) in model.programmer_mode() {
+            lp.send_sysex(msg_type, &data)?;
+        }
+        Ok(lp)
+    }
+    /// Returns a launchpad that writes to the given output instead of a device.
+    /// No input thread is started, so poll_input only returns connection events.
+    /// Failed sends are returned as errors unless set_reconnect is turned on.
+    /// ```
+    /// # use lp_tetris::{mock::MockOutput, GridDisplay, Launchpad};
+    /// let out = MockOutput::new();
+    /// let mut lp = Launchpad::with_output(out.clone());
+    /// lp.clear().unwrap();
+    /// assert_eq!(out.messages(), vec![vec![0xF0, 0x00, 0x20, 0x29, 0x02, 0x18, 0x0E, 0, 0xF7]]);
+    /// assert_eq!(lp.poll_input(), None);
+    /// ```
+    pub fn with_output<O: MidiSink + 'static>(output: O) -> Launchpad {
+        Launchpad::with_output_model(output, LaunchpadModel::Mk2)
+    }
+    /// Same as with_output, but frames messages for the given model
+    /// ```
+    /// # use lp_tetris::{mock::MockOutput, GridDisplay, Launchpad, LaunchpadModel, Pad};
+    /// let out = MockOutput::new();
+    /// let mut lp = Launchpad::with_output_model(out.clone(), LaunchpadModel::X);
+    /// lp.send_pad(Pad { x: 0, y: 0 }, 5).unwrap();
+    /// assert_eq!(out.messages(), vec![vec![0xF0, 0x00, 0x20, 0x29, 0x02, 0x0C, 0x03, 0, 11, 5, 0xF7]]);
+    /// ```
+    pub fn with_output_model<O: MidiSink + 'static>(output: O, model: LaunchpadModel) -> Launchpad {
+        let (events_tx, events_rx) = mpsc::channel();
         Launchpad {
-            conn_out,
+            model,
+            conn_out: Box::new(output),
+            conn_in: None,
             events_rx,
+            _keyboard: None,
+            scrolling: Arc::new(AtomicBool::new(false)),
+            scroll_until: None,
+            frame: FrameBuffer::new(),
+            events_tx,
+            bindings: input::SharedBindings::default(),
+            recorder: Arc::new(Mutex::new(None)),
+            device: None,
+            reconnect: false,
+            connected: true,
+            next_check: Instant::now() + CHECK_INTERVAL,
         }
     }
-    /// Closes the underlying midi connection to the launchpad
+    /// Returns which model of launchpad this is
+    pub fn model(&self) -> LaunchpadModel {
+        self.model
+    }
+    /// Sets what happens when the launchpad goes away, on by default for opened devices.
+    /// When on, a failed send (or the port disappearing) sends ControlEvent::Disconnected,
+    /// and everything sent until it's back is dropped instead of failing.
+    /// poll_input then looks for the device again, and once it's found redraws the last frame
+    /// and sends ControlEvent::Reconnected.
+    /// If there are several of the same model plugged in, it may come back as a different one.
+    pub fn set_reconnect(&mut self, reconnect: bool) {
+        self.reconnect = reconnect;
+    }
+    /// Replaces what the buttons and keyboard do, taking effect immediately
+    pub fn set_bindings(&mut self, bindings: input::Bindings) {
+        match self.bindings.write() {
+            Ok(mut shared) => *shared = bindings,
+            Err(poisoned) => *poisoned.into_inner() = bindings,
+        }
+    }
+    /// Starts recording every MIDI message sent to and received from the launchpad,
+    /// or stops if given None
+    pub fn set_recorder(&mut self, recorder: Option<record::Recorder>) {
+        match self.recorder.lock() {
+            Ok(mut shared) => *shared = recorder,
+            Err(poisoned) => *poisoned.into_inner() = recorder,
+        }
+    }
+    /// Returns false between Disconnected and Reconnected
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+    /// Closes the underlying midi connections to the launchpad
     pub fn close(self) {
-        self.conn_out.close();
+        if let Some(conn_in) = self.conn_in {
+            conn_in.close();
+        }
+        drop(self.conn_out);
     }
     // pub fn poll_inputs(&mut self) -> ControlEvent {
     //     match self.input_buffer.try_recv() {
@@ -137,64 +504,527 @@ impl Launchpad {
 }
 // Render defs
 impl Launchpad {
-    pub fn send_sysex(&mut self, msg_type: u8, data: &[u8]) {
-        let mut msg_data = vec![0xF0, 0x00, 0x20, 0x29, 0x02, 0x18, msg_type];
+    /// Sends a SysEx message with this model's header.
+    /// If it fails, what's on the grid is no longer known, so the next frame is sent in full.
+    /// If reconnecting is on, failures are handled by disconnecting instead of being returned.
+    pub fn send_sysex(&mut self, msg_type: u8, data: &[u8]) -> Result<(), LaunchpadError> {
+        if !self.connected {
+            self.frame.invalidate();
+            return Ok(());
+        }
+        let result = self.write_sysex(msg_type, data);
+        if result.is_err() {
+            self.frame.invalidate();
+            if self.reconnect {
+                self.disconnect();
+                return Ok(());
+            }
+        }
+        result
+    }
+    /// Sends a SysEx message with this model's header, and nothing else
+    fn write_sysex(&mut self, msg_type: u8, data: &[u8]) -> Result<(), LaunchpadError> {
+        let mut msg_data = self.model.sysex_header().to_vec();
+        msg_data.push(msg_type);
         msg_data.extend(data);
         msg_data.push(0xF7);
-        self.conn_out.send(&msg_data).unwrap();
-    }
-    pub fn clear(&mut self) {
-        self.send_sysex(0x0E, &[0]);
-    }
-    pub fn send_note(&mut self, note: u8, velocity: u8) {
-        self.send_sysex(0x0A, &[note, velocity]);
-    }
-    pub fn send_matrix(&mut self, matrix: array2d::Array2D<u8>) {
-        let msg = {
-            let mut msg = Vec::new();
-            for y in 0..8 {
-                for x in 0..8 {
-                    let pad = Pad { x, y };
-                    msg.push(pad.note());
-                    msg.push(matrix[(y as usize, x as usize)]);
+        self.write_raw(&msg_data)
+    }
+    /// Sends a raw message, recording it if there's a recorder
+    fn write_raw(&mut self, msg: &[u8]) -> Result<(), LaunchpadError> {
+        record(&self.recorder, record::Direction::Out, msg);
+        self.conn_out.send(msg)
+    }
+    pub fn send_note(&mut self, note: u8, velocity: u8) -> Result<(), LaunchpadError> {
+        let (msg_type, data) = self.model.led_message(&[(note, velocity)]);
+        self.send_sysex(msg_type, &data)?;
+        if let Some(Button::Grid(pad)) = Button::from_index(self.model, note) {
+            self.frame.set(pad, velocity);
+        }
+        Ok(())
+    }
+    /// Sets each (note, colour) pair.
+    /// Palette and RGB colours use different commands, so this sends up to two messages.
+    pub fn send_colors(&mut self, leds: &[(u8, Color)]) -> Result<(), LaunchpadError> {
+        let mut palette = Vec::new();
+        let mut rgb = Vec::new();
+        for (note, color) in leds {
+            match *color {
+                Color::Palette(index) => palette.push((*note, index)),
+                Color::Rgb(r, g, b) => rgb.push((*note, r, g, b)),
+            }
+        }
+        if !palette.is_empty() {
+            let (msg_type, data) = self.model.led_message(&palette);
+            self.send_sysex(msg_type, &data)?;
+            for (note, index) in palette {
+                if let Some(Button::Grid(pad)) = Button::from_index(self.model, note) {
+                    self.frame.set(pad, index);
                 }
             }
-            msg
+        }
+        if !rgb.is_empty() {
+            let (msg_type, data) = self.model.rgb_message(&rgb);
+            self.send_sysex(msg_type, &data)?;
+            // The frame buffer only knows palette colours
+            self.frame.invalidate();
+        }
+        Ok(())
+    }
+    /// Resends the last frame in full, for when the device has lost track of what it's showing.
+    /// Does nothing if nothing has been drawn yet.
+    pub fn refresh(&mut self) -> Result<(), LaunchpadError> {
+        if let Some(last) = self.frame.last().cloned() {
+            self.frame.invalidate();
+            self.send_matrix(last)?;
+        }
+        Ok(())
+    }
+}
+// Flash/pulse defs
+impl Launchpad {
+    /// Flashes a pad between the given palette colour and off, in time with MIDI clock
+    /// (or 120bpm without one). Sending the pad a normal colour stops it.
+    /// Fails with NoSuchButton if the pad is off the grid.
+    pub fn flash_pad(&mut self, pad: Pad, velocity: u8) -> Result<(), LaunchpadError> {
+        let button = Button::Grid(pad);
+        let index = button
+            .index(self.model)
+            .ok_or(LaunchpadError::NoSuchButton(button))?;
+        let (msg_type, data) = self.model.flash_message(&[(index, velocity)]);
+        self.send_sysex(msg_type, &data)?;
+        self.frame.invalidate();
+        Ok(())
+    }
+    /// Pulses a pad's brightness in the given palette colour.
+    /// Sending the pad a normal colour stops it.
+    /// Fails with NoSuchButton if the pad is off the grid.
+    pub fn pulse_pad(&mut self, pad: Pad, velocity: u8) -> Result<(), LaunchpadError> {
+        let button = Button::Grid(pad);
+        let index = button
+            .index(self.model)
+            .ok_or(LaunchpadError::NoSuchButton(button))?;
+        let (msg_type, data) = self.model.pulse_message(&[(index, velocity)]);
+        self.send_sysex(msg_type, &data)?;
+        self.frame.invalidate();
+        Ok(())
+    }
+    /// Flashes every pad in the matrix that isn't 0.
+    /// Pads that are 0 are left showing whatever they were.
+    pub fn flash_matrix(&mut self, matrix: array2d::Array2D<u8>) -> Result<(), LaunchpadError> {
+        let leds = lit_pads(&matrix);
+        if !leds.is_empty() {
+            let (msg_type, data) = self.model.flash_message(&leds);
+            self.send_sysex(msg_type, &data)?;
+            self.frame.invalidate();
+        }
+        Ok(())
+    }
+    /// Pulses every pad in the matrix that isn't 0.
+    /// Pads that are 0 are left showing whatever they were.
+    pub fn pulse_matrix(&mut self, matrix: array2d::Array2D<u8>) -> Result<(), LaunchpadError> {
+        let leds = lit_pads(&matrix);
+        if !leds.is_empty() {
+            let (msg_type, data) = self.model.pulse_message(&leds);
+            self.send_sysex(msg_type, &data)?;
+            self.frame.invalidate();
+        }
+        Ok(())
+    }
+}
+// Text defs
+impl Launchpad {
+    /// Stops any scrolling text
+    pub fn stop_scroll(&mut self) -> Result<(), LaunchpadError> {
+        let (msg_type, data) = self.model.scroll_message("", 0, 1, false);
+        self.send_sysex(msg_type, &data)?;
+        self.scrolling.store(false, Ordering::SeqCst);
+        self.scroll_until = None;
+        Ok(())
+    }
+}
+/// Lists the output ports midi_out can see
+fn output_ports(midi_out: &midir::MidiOutput) -> Vec<PortInfo> {
+    (0..midi_out.port_count())
+        .filter_map(|index| {
+            let name = midi_out.port_name(index).ok()?;
+            let model = LaunchpadModel::from_port_name(&name);
+            Some(PortInfo { index, name, model })
+        })
+        .collect()
+}
+/// Opens the given output port and the input port that goes with it.
+/// Button presses are looked up in bindings and sent down events_tx,
+/// and scrolling is cleared when the device says it's finished scrolling text.
+fn connect(
+    port: &PortInfo,
+    model: LaunchpadModel,
+    events_tx: mpsc::Sender<InputEvent>,
+    scrolling: Arc<AtomicBool>,
+    bindings: input::SharedBindings,
+    recorder: Arc<Mutex<Option<record::Recorder>>>,
+) -> Result<(midir::MidiOutputConnection, midir::MidiInputConnection<()>), LaunchpadError> {
+    let midi_out =
+        midir::MidiOutput::new("lp_tetris").map_err(|e| LaunchpadError::MidiInit(e.to_string()))?;
+    let outputs = output_ports(&midi_out);
+    // The ports may have changed since the caller listed them
+    if !outputs.contains(port) {
+        return Err(LaunchpadError::NoDevice);
+    }
+    // Which launchpad of this model it is, to find the matching input on systems that
+    // name the two ports differently
+    let rank = outputs
+        .iter()
+        .filter(|other| other.index < port.index && other.model == port.model)
+        .count();
+    let conn_out = midi_out
+        .connect(port.index, "lp_tetris")
+        .map_err(|_| LaunchpadError::PortBusy(port.name.clone()))?;
+
+    let mut midi_in =
+        midir::MidiInput::new("lp_tetris").map_err(|e| LaunchpadError::InputInit(e.to_string()))?;
+    let inputs: Vec<(usize, String)> = (0..midi_in.port_count())
+        .filter_map(|index| Some((index, midi_in.port_name(index).ok()?)))
+        .filter(|(_, name)| LaunchpadModel::from_port_name(name) == Some(model))
+        .collect();
+    let (in_port, _) = inputs
+        .iter()
+        .find(|(_, name)| *name == port.name)
+        .or_else(|| inputs.get(rank))
+        .ok_or_else(|| LaunchpadError::InputInit(format!("no input port for {}", port.name)))?;
+    midi_in.ignore(midir::Ignore::None);
+    let scroll_done = model.scroll_done_command().map(|command| {
+        let mut msg = model.sysex_header().to_vec();
+        msg.extend(&[command, 0xF7]);
+        msg
+    });
+    let conn_in = midi_in
+        .connect(
+            *in_port,
+            "lp_tetris",
+            move |_, msg, _| {
+                record(&recorder, record::Direction::In, msg);
+                if scroll_done.as_deref() == Some(msg) {
+                    scrolling.store(false, Ordering::SeqCst);
+                }
+                let event = match bindings.read() {
+                    Ok(bindings) => midi_map(model, msg, &bindings),
+                    Err(poisoned) => midi_map(model, msg, &poisoned.into_inner()),
+                };
+                if let Some(event) = event {
+                    events_tx.send(event).ok();
+                }
+            },
+            (),
+        )
+        .map_err(|e| LaunchpadError::InputInit(e.to_string()))?;
+    Ok((conn_out, conn_in))
+}
+/// Writes a message to the recorder if there is one.
+/// A recording that can't be written to shouldn't stop the game, so errors are ignored.
+fn record(recorder: &Mutex<Option<record::Recorder>>, direction: record::Direction, msg: &[u8]) {
+    let recorder = match recorder.lock() {
+        Ok(recorder) => recorder,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(recorder) = recorder.as_ref() {
+        recorder.record(direction, msg).ok();
+    }
+}
+/// Cuts a matrix down to at most width by height, keeping the bottom left corner
+fn crop(matrix: array2d::Array2D<u8>, width: usize, height: usize) -> array2d::Array2D<u8> {
+    if matrix.num_rows() <= height && matrix.num_columns() <= width {
+        return matrix;
+    }
+    let rows = matrix.num_rows().min(height);
+    let columns = matrix.num_columns().min(width);
+    let matrix = &matrix;
+    array2d::Array2D::from_iter_row_major(
+        (0..rows).flat_map(|y| (0..columns).map(move |x| matrix[(y, x)])),
+        rows,
+        columns,
+    )
+}
+/// Returns (note, colour) for every pad on the grid that the matrix lights,
+/// ignoring anything past the grid
+fn lit_pads(matrix: &array2d::Array2D<u8>) -> Vec<(u8, u8)> {
+    let mut leds = Vec::new();
+    for y in 0..8 {
+        for x in 0..8 {
+            match matrix.get(y as usize, x as usize) {
+                Some(&color) if color != 0 => leds.push((Pad { x, y }.note(), color)),
+                _ => (),
+            }
+        }
+    }
+    leds
+}
+impl Controller for Launchpad {
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        Launchpad::poll_event(self)
+    }
+    fn pulse_matrix(&mut self, matrix: array2d::Array2D<u8>) -> Result<(), LaunchpadError> {
+        Launchpad::pulse_matrix(self, matrix)
+    }
+    fn stop_scroll(&mut self) -> Result<(), LaunchpadError> {
+        Launchpad::stop_scroll(self)
+    }
+    fn set_bindings(&mut self, bindings: input::Bindings) {
+        Launchpad::set_bindings(self, bindings)
+    }
+    fn set_recorder(&mut self, recorder: Option<record::Recorder>) {
+        Launchpad::set_recorder(self, recorder)
+    }
+}
+impl GridDisplay for Launchpad {
+    type Error = LaunchpadError;
+
+    fn clear(&mut self) -> Result<(), LaunchpadError> {
+        let (msg_type, data) = self.model.clear_message();
+        self.send_sysex(msg_type, &data)?;
+        self.frame.fill(0);
+        Ok(())
+    }
+    /// Fails with NoSuchButton if the pad is off the grid
+    /// ```
+    /// # use lp_tetris::{mock::MockOutput, Button, GridDisplay, Launchpad, LaunchpadError, Pad};
+    /// let out = MockOutput::new();
+    /// let mut lp = Launchpad::with_output(out.clone());
+    /// let pad = Pad { x: 8, y: 0 };
+    /// assert_eq!(lp.send_pad(pad, 5), Err(LaunchpadError::NoSuchButton(Button::Grid(pad))));
+    /// assert_eq!(out.updates(), vec![]);
+    /// ```
+    fn send_pad(&mut self, pad: Pad, velocity: u8) -> Result<(), LaunchpadError> {
+        self.send_button(Button::Grid(pad), velocity)
+    }
+    /// Sends only the pads that changed since the last frame.
+    /// Use refresh to resend everything.
+    /// Anything past the top or right of the grid is cut off.
+    fn send_matrix(&mut self, matrix: array2d::Array2D<u8>) -> Result<(), LaunchpadError> {
+        let (width, height) = self.size();
+        let leds: Vec<(u8, u8)> = self
+            .frame
+            .diff(crop(matrix, width, height))
+            .into_iter()
+            .map(|(pad, color)| (pad.note(), color))
+            .collect();
+        if !leds.is_empty() {
+            let (msg_type, data) = self.model.led_message(&leds);
+            self.send_sysex(msg_type, &data)?;
+        }
+        Ok(())
+    }
+    /// Fails with NoSuchButton if the button isn't on a launchpad
+    fn send_button(&mut self, button: Button, velocity: u8) -> Result<(), LaunchpadError> {
+        let index = button
+            .index(self.model)
+            .ok_or(LaunchpadError::NoSuchButton(button))?;
+        self.send_note(index, velocity)
+    }
+    /// Fails with NoSuchButton if the pad is off the grid
+    fn send_pad_color(&mut self, pad: Pad, color: Color) -> Result<(), LaunchpadError> {
+        let button = Button::Grid(pad);
+        let index = button
+            .index(self.model)
+            .ok_or(LaunchpadError::NoSuchButton(button))?;
+        self.send_colors(&[(index, color)])
+    }
+    /// Pads the matrix doesn't reach are left alone, and anything past the grid is cut off
+    fn send_color_matrix(&mut self, matrix: array2d::Array2D<Color>) -> Result<(), LaunchpadError> {
+        let mut leds = Vec::new();
+        for y in 0..8 {
+            for x in 0..8 {
+                if let Some(color) = matrix.get(y as usize, x as usize) {
+                    leds.push((Pad { x, y }.note(), *color));
+                }
+            }
+        }
+        self.send_colors(&leds)
+    }
+    /// Scrolls text across the grid.
+    /// is_scrolling can only tell when it's finished if the input port is open,
+    /// otherwise it's assumed to be done straight away.
+    /// ```
+    /// # use lp_tetris::{mock::{MockOutput, Update}, GridDisplay, Launchpad};
+    /// let out = MockOutput::new();
+    /// let mut lp = Launchpad::with_output(out.clone());
+    /// lp.scroll_text("GAME OVER", 5, 4, false).unwrap();
+    /// assert_eq!(out.updates(), vec![Update::Scroll { text: "GAME OVER".into(), velocity: 5, speed: 4, looping: false }]);
+    /// assert!(!lp.is_scrolling());
+    /// ```
+    fn scroll_text(
+        &mut self,
+        text: &str,
+        velocity: u8,
+        speed: u8,
+        looping: bool,
+    ) -> Result<(), LaunchpadError> {
+        let (msg_type, data) = self.model.scroll_message(text, velocity, speed, looping);
+        self.send_sysex(msg_type, &data)?;
+        self.frame.invalidate();
+        if self.conn_in.is_none() || !self.connected {
+            return Ok(());
+        }
+        self.scrolling.store(true, Ordering::SeqCst);
+        self.scroll_until = match self.model.scroll_done_command() {
+            Some(_) => None,
+            None if looping => None,
+            None => Some(Instant::now() + self.model.scroll_duration(text, speed)),
         };
-        self.send_sysex(0x0A, &msg);
+        Ok(())
+    }
+    fn is_scrolling(&self) -> bool {
+        match self.scroll_until {
+            Some(until) if Instant::now() >= until => false,
+            _ => self.scrolling.load(Ordering::SeqCst),
+        }
     }
 }
 // Input defs
 impl Launchpad {
-    /// Get next ControlEvent
-    pub fn poll_input(&self) -> Option<ControlEvent> {
+    /// Get next ControlEvent, skipping releases.
+    /// This is also where the connection is checked and, if it's lost, reestablished.
+    pub fn poll_input(&mut self) -> Option<ControlEvent> {
+        loop {
+            match self.poll_event()? {
+                InputEvent {
+                    event,
+                    pressed: true,
+                    ..
+                } => return Some(event),
+                _ => continue,
+            }
+        }
+    }
+    /// Get next InputEvent, presses and releases both.
+    /// Like poll_input, this checks the connection.
+    pub fn poll_event(&mut self) -> Option<InputEvent> {
+        if self.reconnect && Instant::now() >= self.next_check {
+            self.next_check = Instant::now() + CHECK_INTERVAL;
+            self.check_connection();
+        }
         self.events_rx.try_recv().ok()
     }
 }
-/// Given an rdev::EventType, return a ControlEvent.
-/// This is the method to modify if you want to change/add input mappings.
-///
-pub fn input_map(event: RawEvent) -> Option<ControlEvent> {
-    match event {
-        RawEvent::KeyboardEvent(_, KeyId::A, State::Pressed) => Some(ControlEvent::RotateLeft),
-        RawEvent::KeyboardEvent(_, KeyId::D, State::Pressed) => Some(ControlEvent::RotateRight),
-        RawEvent::KeyboardEvent(_, KeyId::Left, State::Pressed) => Some(ControlEvent::MoveLeft),
-        RawEvent::KeyboardEvent(_, KeyId::Right, State::Pressed) => Some(ControlEvent::MoveRight),
-        RawEvent::KeyboardEvent(_, KeyId::Up, State::Pressed) => Some(ControlEvent::MoveUp),
-        RawEvent::KeyboardEvent(_, KeyId::Down, State::Pressed) => Some(ControlEvent::MoveDown),
-        RawEvent::KeyboardEvent(_, KeyId::Space, State::Pressed) => Some(ControlEvent::DropBlock),
-        // RawEvent::KeyboardEvent(_, KeyId::One, State::Pressed) => Some(ControlEvent::SpeedChange(0)),
-        // RawEvent::KeyboardEvent(_, KeyId::Two, State::Pressed) => Some(ControlEvent::SpeedChange(1)),
-        // RawEvent::KeyboardEvent(_, KeyId::Three, State::Pressed) => Some(ControlEvent::SpeedChange(2)),
-        // RawEvent::KeyboardEvent(_, KeyId::Four, State::Pressed) => Some(ControlEvent::SpeedChange(3)),
-        // RawEvent::KeyboardEvent(_, KeyId::Five, State::Pressed) => Some(ControlEvent::SpeedChange(4)),
-        // RawEvent::KeyboardEvent(_, KeyId::Six, State::Pressed) => Some(ControlEvent::SpeedChange(5)),
-        // RawEvent::KeyboardEvent(_, KeyId::Seven, State::Pressed) => Some(ControlEvent::SpeedChange(6)),
-        // RawEvent::KeyboardEvent(_, KeyId::Eight, State::Pressed) => Some(ControlEvent::SpeedChange(7)),
-        // RawEvent::KeyboardEvent(_, KeyId::Nine, State::Pressed) => Some(ControlEvent::SpeedChange(8)),
-        RawEvent::KeyboardEvent(_, KeyId::Backspace, State::Pressed) => {
-            Some(ControlEvent::ExitGame)
-        }
-        _ => None,
+// Connection defs
+impl Launchpad {
+    /// Notes that the device has gone away
+    fn disconnect(&mut self) {
+        self.connected = false;
+        self.frame.invalidate();
+        // Whatever was scrolling went with it
+        self.scrolling.store(false, Ordering::SeqCst);
+        self.scroll_until = None;
+        // Try to get it back on the next poll
+        self.next_check = Instant::now();
+        self.events_tx
+            .send(InputEvent::press(ControlEvent::Disconnected))
+            .ok();
+    }
+    /// Looks for the port going away while connected, or coming back while not
+    fn check_connection(&mut self) {
+        let device = match &self.device {
+            Some(device) => device,
+            // Without a port to look for, all we can do is try sending
+            None if self.connected => return,
+            None => return self.restore(),
+        };
+        let ports = output_ports(&device.watcher);
+        if self.connected {
+            if !ports.iter().any(|port| port.name == device.port.name) {
+                self.disconnect();
+            }
+            return;
+        }
+        // Names can change when a device is plugged back in, so fall back to the model
+        let found = ports
+            .iter()
+            .find(|port| port.name == device.port.name)
+            .or_else(|| ports.iter().find(|port| port.model == Some(self.model)));
+        let port = match found {
+            Some(port) => port.clone(),
+            None => return,
+        };
+        let events_tx = self.events_tx.clone();
+        if let Ok((conn_out, conn_in)) = connect(
+            &port,
+            self.model,
+            events_tx,
+            self.scrolling.clone(),
+            self.bindings.clone(),
+            self.recorder.clone(),
+        ) {
+            self.conn_out = Box::new(conn_out);
+            self.conn_in = Some(conn_in);
+            if let Some(device) = self.device.as_mut() {
+                device.port = port;
+            }
+            self.restore();
+        }
     }
+    /// Puts the device back how it was before it was disconnected.
+    /// Stays disconnected if that fails.
+    fn restore(&mut self) {
+        // The launchpad forgets everything when it's unplugged
+        let mut messages = Vec::new();
+        if self.device.is_some() {
+            messages.extend(self.model.programmer_mode());
+        }
+        self.frame.invalidate();
+        match self.frame.last().cloned() {
+            Some(last) => {
+                let leds: Vec<(u8, u8)> = self
+                    .frame
+                    .diff(last)
+                    .into_iter()
+                    .map(|(pad, color)| (pad.note(), color))
+                    .collect();
+                messages.push(self.model.led_message(&leds));
+            }
+            None => {
+                messages.push(self.model.clear_message());
+                self.frame.fill(0);
+            }
+        }
+        for (msg_type, data) in messages {
+            if self.write_sysex(msg_type, &data).is_err() {
+                self.frame.invalidate();
+                return;
+            }
+        }
+        self.connected = true;
+        self.events_tx
+            .send(InputEvent::press(ControlEvent::Reconnected))
+            .ok();
+    }
+}
+/// Given a raw MIDI message from the launchpad, return the ControlEvent it's bound to,
+/// and whether the button was pressed or released.
+/// By default the arrow buttons move, the top two scene buttons rotate right/left,
+/// the bottom scene button drops, and the rightmost top button ends the game.
+/// ```
+/// # use lp_tetris::{input::Bindings, midi_map, ControlEvent, InputEvent, LaunchpadModel};
+/// let bindings = Bindings::default();
+/// let map = |model, msg: &[u8]| {
+///     midi_map(model, msg, &bindings).map(|input: InputEvent| (input.event, input.pressed))
+/// };
+/// assert_eq!(map(LaunchpadModel::Mk2, &[0xB0, 106, 127]), Some((ControlEvent::MoveLeft, true)));
+/// assert_eq!(map(LaunchpadModel::Mk2, &[0x90, 89, 127]), Some((ControlEvent::RotateRight, true)));
+/// assert_eq!(map(LaunchpadModel::Mk2, &[0x90, 89, 0]), Some((ControlEvent::RotateRight, false)));
+/// assert_eq!(map(LaunchpadModel::X, &[0xB0, 93, 127]), Some((ControlEvent::MoveLeft, true)));
+/// assert_eq!(map(LaunchpadModel::X, &[0xB0, 89, 127]), Some((ControlEvent::RotateRight, true)));
+/// assert_eq!(map(LaunchpadModel::X, &[0x90, 44, 127]), None);
+/// ```
+pub fn midi_map(
+    model: LaunchpadModel,
+    msg: &[u8],
+    bindings: &input::Bindings,
+) -> Option<InputEvent> {
+    let (button, pressed) = Button::from_midi(model, msg)?;
+    let event = bindings.get(input::Trigger::Button(button))?;
+    Some(InputEvent {
+        event,
+        pressed,
+        time: Instant::now(),
+    })
 }
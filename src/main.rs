@@ -16,23 +16,38 @@
  */
 
 use array2d::Array2D;
-use lp_tetris::{ControlEvent, Launchpad};
+use lp_tetris::{
+    input::{self, Bindings, BindingsError},
+    mock::{self, MockOutput},
+    palette,
+    record::{self, Direction, Recorder},
+    viewport::{Offscreen, Rect, Viewport},
+    AutoRepeat, Button, ControlEvent, Controller, GridDisplay, InputEvent, Launchpad,
+    LaunchpadError, MidiSink, MultiLaunchpad, Status, TerminalDisplay,
+};
+use std::env;
+use std::fmt;
+use std::io;
+use std::iter;
+use std::process;
+use std::sync::{mpsc, Arc, RwLock};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 mod tetris;
 use rand::Rng;
-use tetris::CollisionResult;
+use tetris::{CollisionResult, Randomizer, RotationSystem};
 
 #[allow(unused)]
-fn run_color(lp: &mut Launchpad, c: u8) {
+fn run_color(lp: &mut Launchpad, c: u8) -> Result<(), LaunchpadError> {
     for i in 0x29..0x31 {
-        lp.send_note(i, c);
+        lp.send_note(i, c)?;
         sleep(Duration::from_millis(50));
     }
     for i in 0x33..0x3B {
-        lp.send_note(i, c);
+        lp.send_note(i, c)?;
         sleep(Duration::from_millis(50));
     }
+    Ok(())
 }
 #[allow(unused)]
 fn test_matrix() -> Array2D<u8> {
@@ -41,21 +56,586 @@ fn test_matrix() -> Array2D<u8> {
 }
 
 fn main() {
-    let mut rng = rand::thread_rng();
-    let mut current_piece = tetris::Piece::new(rng.gen());
-    let mut pos_x: usize = 3;
-    let mut pos_y: usize = 5;
+    let args: Vec<String> = env::args().skip(1).collect();
+    let bindings = match load_bindings() {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+    let rules = match rules() {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(2);
+        }
+    };
+    let opened = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => Launchpad::open(),
+        ["--list"] => {
+            list_ports();
+            return;
+        }
+        ["--terminal"] | ["--terminal", _] => {
+            let size = match args.get(1) {
+                Some(size) => parse_size(size),
+                None => Some((8, 8)),
+            };
+            let (width, height) = match size {
+                Some(size) => size,
+                None => {
+                    eprintln!("Error: expected a size like 10x20, at least 4x4");
+                    process::exit(2);
+                }
+            };
+            if let Err(e) = play_terminal(bindings, width, height, rules) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        ["--port", port] => open_port(port),
+        ["--multi", layout] => {
+            match MultiLaunchpad::open_layout(&layout_arg(layout)) {
+                Ok(multi) => run(multi, bindings, rules),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+            return;
+        }
+        ["--replay", path, "--mock"] => {
+            if let Err(e) = replay_mock(path) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        ["--replay", path] | ["--replay", path, "--port", _] => {
+            let opened = match args.get(3) {
+                Some(port) => open_port(port),
+                None => Launchpad::open(),
+            };
+            if let Err(e) = opened.map_err(|e| e.to_string()).and_then(|mut lp| {
+                println!("Replaying {}", path);
+                replay(&mut [&mut lp], path)
+            }) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        ["--replay", path, "--multi", layout] => {
+            let opened = MultiLaunchpad::open_layout(&layout_arg(layout));
+            if let Err(e) = opened.map_err(|e| e.to_string()).and_then(|mut multi| {
+                println!("Replaying {}", path);
+                let mut sinks: Vec<&mut dyn MidiSink> = multi
+                    .launchpads()
+                    .map(|lp| lp as &mut dyn MidiSink)
+                    .collect();
+                replay(&mut sinks, path)
+            }) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        _ => {
+            eprintln!(
+                "Usage: lp_tetris [--list | --port <index or name> | --terminal [WIDTHxHEIGHT]]"
+            );
+            eprintln!("       lp_tetris --multi <layout>");
+            eprintln!(
+                "       lp_tetris --replay <recording> [--mock | --port <index or name> | --multi <layout>]"
+            );
+            process::exit(2);
+        }
+    };
+    match opened {
+        Ok(lp) => run(lp, bindings, rules),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Sets up an opened launchpad (or several) and plays a game on it,
+/// exiting if anything goes wrong
+fn run<C: Controller>(mut lp: C, bindings: Bindings, rules: Rules)
+where
+    C::Error: fmt::Display,
+{
+    lp.set_bindings(bindings);
+    if let Some(path) = env::var_os("LP_TETRIS_RECORD") {
+        match Recorder::create(&path) {
+            Ok(recorder) => lp.set_recorder(Some(recorder)),
+            Err(e) => {
+                eprintln!("Error: couldn't record to {:?}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+    println!("Connection open!!");
+    if let Err(e) = play(&mut lp, rules) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Parses a board size like 10x20. Anything smaller than 4x4 can't fit every piece,
+/// and the hidden rows on top have to fit in 255 with the rest.
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    if width < 4 || height < 4 || width > 255 || height > 255 - tetris::BUFFER_ROWS {
+        return None;
+    }
+    Some((width, height))
+}
+
+/// Parses the layout given to --multi, exiting if it's malformed
+fn layout_arg(layout: &str) -> Vec<Vec<String>> {
+    match parse_layout(layout) {
+        Some(layout) => layout,
+        None => {
+            eprintln!("Error: expected launchpad ports laid out like 1,2/0,3");
+            process::exit(2);
+        }
+    }
+}
+
+/// Parses a launchpad layout like 1,2/0,3: rows top to bottom split by /,
+/// each a list of port indices or names split by commas
+fn parse_layout(layout: &str) -> Option<Vec<Vec<String>>> {
+    layout
+        .split('/')
+        .map(|row| {
+            row.split(',')
+                .map(|port| match port.trim() {
+                    "" => None,
+                    port => Some(port.to_string()),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// How a game is played
+struct Rules {
+    /// The well's size, or None for the size of the display
+    well: Option<(usize, usize)>,
+    rotation: Box<dyn RotationSystem>,
+    randomizer: Box<dyn Randomizer>,
+    /// What the randomizer was seeded with, so the game can be dealt again
+    seed: u64,
+}
+
+/// Reads the rules from the environment
+fn rules() -> Result<Rules, String> {
+    let seed = match env::var("LP_TETRIS_SEED") {
+        Ok(seed) => seed
+            .parse()
+            .map_err(|_| format!("LP_TETRIS_SEED should be a number, not {:?}", seed))?,
+        Err(_) => rand::thread_rng().gen(),
+    };
+    Ok(Rules {
+        well: well_size()?,
+        rotation: rotation_system()?,
+        randomizer: randomizer(seed)?,
+        seed,
+    })
+}
+
+/// Reads the randomizer from LP_TETRIS_RANDOMIZER, a 7-bag if it isn't set
+fn randomizer(seed: u64) -> Result<Box<dyn Randomizer>, String> {
+    let name = env::var("LP_TETRIS_RANDOMIZER").unwrap_or_else(|_| "7bag".to_string());
+    tetris::randomizer(&name, seed).ok_or_else(|| {
+        format!(
+            "LP_TETRIS_RANDOMIZER should be 7bag, 14bag, tgm or random, not {:?}",
+            name
+        )
+    })
+}
+
+/// Reads the rotation system from LP_TETRIS_ROTATION, SRS if it isn't set
+fn rotation_system() -> Result<Box<dyn RotationSystem>, String> {
+    match env::var("LP_TETRIS_ROTATION") {
+        Ok(name) => tetris::rotation_system(&name).ok_or_else(|| {
+            format!(
+                "LP_TETRIS_ROTATION should be srs, nrs or ars, not {:?}",
+                name
+            )
+        }),
+        Err(_) => Ok(Box::new(tetris::Srs)),
+    }
+}
+
+/// Reads the well size from LP_TETRIS_WELL, e.g. 10x20.
+/// None if it isn't set, and the well is the size of the display.
+fn well_size() -> Result<Option<(usize, usize)>, String> {
+    match env::var("LP_TETRIS_WELL") {
+        Ok(size) => match parse_size(&size) {
+            Some(size) => Ok(Some(size)),
+            None => Err(format!(
+                "LP_TETRIS_WELL should be a size like 10x20, at least 4x4, not {:?}",
+                size
+            )),
+        },
+        Err(_) => Ok(None),
+    }
+}
+
+/// Opens the launchpad on the port with the given index or name
+fn open_port(port: &str) -> Result<Launchpad, LaunchpadError> {
+    match port.parse() {
+        Ok(index) => Launchpad::open_by_index(index),
+        Err(_) => Launchpad::open_by_name(port),
+    }
+}
+
+/// Plays a recording back on the launchpads, one per tile, at the speed it was recorded
+fn replay(sinks: &mut [&mut dyn MidiSink], path: &str) -> Result<(), String> {
+    let entries = record::load(path).map_err(|e| e.to_string())?;
+    record::replay(&entries, sinks, true).map_err(|e| e.to_string())
+}
+
+/// Plays a recording back on mock launchpads, one per tile,
+/// printing every message each was sent and what their grids ended up showing
+fn replay_mock(path: &str) -> io::Result<()> {
+    let entries = record::load(path)?;
+    let tiles = entries
+        .iter()
+        .map(|entry| entry.tile + 1)
+        .max()
+        .unwrap_or(1);
+    let sent = entries
+        .iter()
+        .filter(|entry| entry.direction == Direction::Out);
+    // Only what the mock understands is shown at the end, setup like programmer mode is skipped
+    let mut shown: Vec<MockOutput> = (0..tiles).map(|_| MockOutput::new()).collect();
+    for entry in sent {
+        let time = entry.time.as_secs_f64();
+        let tile = match tiles {
+            1 => String::new(),
+            _ => format!("[{}] ", entry.tile),
+        };
+        match mock::decode(&entry.msg) {
+            Some(update) => {
+                println!("{:10.6} {}{:?}", time, tile, update);
+                shown[entry.tile]
+                    .send(&entry.msg)
+                    .map_err(io::Error::other)?;
+            }
+            None => println!("{:10.6} {}{:02X?}", time, tile, entry.msg),
+        }
+    }
+    for (tile, shown) in shown.iter().enumerate() {
+        if tiles > 1 {
+            println!("Tile {}:", tile);
+        }
+        // Top row first, the way it looks on the device
+        let state = shown.state();
+        for row in state.as_rows().iter().rev() {
+            let row: Vec<String> = row.iter().map(|color| format!("{:3}", color)).collect();
+            println!("{}", row.join(" "));
+        }
+    }
+    Ok(())
+}
+
+/// Reads the bindings file named by LP_TETRIS_BINDINGS, if there is one,
+/// using the profile named by LP_TETRIS_PROFILE or [default]
+fn load_bindings() -> Result<Bindings, BindingsError> {
+    let profile = env::var("LP_TETRIS_PROFILE").unwrap_or_else(|_| "default".to_string());
+    match env::var_os("LP_TETRIS_BINDINGS") {
+        Some(path) => Bindings::load(path, &profile),
+        None if profile == "default" => Ok(Bindings::default()),
+        None => Err(BindingsError::NoProfile(profile)),
+    }
+}
+
+/// Prints every MIDI output port, and which launchpad it is if it's one
+fn list_ports() {
+    match Launchpad::list_ports() {
+        Ok(ports) => {
+            for port in ports {
+                match port.model {
+                    Some(model) => println!("{}: {} ({:?})", port.index, port.name, model),
+                    None => println!("{}: {}", port.index, port.name),
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Plays one game on the launchpad (or several) and shows the score
+fn play<C: Controller>(lp: &mut C, rules: Rules) -> Result<(), C::Error> {
+    lp.clear()?;
+    let seed = rules.seed;
+    let outcome = gameloop(lp, rules, auto_repeat(), C::poll_event)?;
+    if let Some(final_frame) = outcome.final_frame {
+        lp.pulse_matrix(final_frame)?;
+        sleep(Duration::from_millis(2000));
+        lp.scroll_text(
+            &format!("GAME OVER  SCORE {}", outcome.score),
+            palette::RED,
+            5,
+            false,
+        )?;
+        // Wait for the message to finish, unless the player wants to leave
+        while lp.is_scrolling() {
+            if let Some(ControlEvent::ExitGame) = lp.poll_input() {
+                lp.stop_scroll()?;
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        lp.clear()?;
+    }
+    println!("Score: {}", outcome.score);
+    println!("Seed: {}", seed);
+    Ok(())
+}
+
+/// Plays one game in the terminal instead of on a launchpad.
+/// The display is `width` by `height`.
+fn play_terminal(bindings: Bindings, width: usize, height: usize, rules: Rules) -> io::Result<()> {
+    let (events_tx, events_rx) = mpsc::channel();
+    // Held until the end, the terminal backend puts the terminal back when it's dropped
+    let bindings = Arc::new(RwLock::new(bindings));
+    if let Err(e) = input::spawn_gamepads(events_tx.clone(), bindings.clone()) {
+        eprintln!("Couldn't read gamepads: {}", e);
+    }
+    let (_, _keyboard) = input::spawn_detected(events_tx, bindings)?;
+    let mut display = TerminalDisplay::new(width, height)?;
+    let seed = rules.seed;
+    let outcome = gameloop(&mut display, rules, auto_repeat(), |_| {
+        events_rx.try_recv().ok()
+    })?;
+    if outcome.final_frame.is_some() {
+        display.scroll_text(
+            &format!("GAME OVER  SCORE {}", outcome.score),
+            palette::RED,
+            5,
+            false,
+        )?;
+        sleep(Duration::from_millis(2000));
+    }
+    drop(display);
+    println!("Score: {}", outcome.score);
+    println!("Seed: {}", seed);
+    Ok(())
+}
+
+/// Points for clearing 1, 2, 3 or 4 rows at once, multiplied by the level
+const LINE_SCORES: [u32; 5] = [0, 40, 100, 300, 1200];
+/// How many rows need clearing to go up a level
+const LINES_PER_LEVEL: u32 = 10;
+
+/// How a game ended
+struct Outcome {
+    /// The last frame if the game was lost, or None if the player quit
+    final_frame: Option<Array2D<u8>>,
+    score: u32,
+}
+
+/// Colour of the level meter on the scene buttons
+const LEVEL_METER_COLOR: u8 = palette::GREEN;
+
+/// Lights one scene button per level, from the bottom up
+fn show_level<D: GridDisplay>(display: &mut D, level: u32) -> Result<(), D::Error> {
+    for n in 0..8 {
+        let color = if n < level {
+            LEVEL_METER_COLOR
+        } else {
+            palette::OFF
+        };
+        display.send_button(Button::Scene(n as u8), color)?;
+    }
+    Ok(())
+}
+
+/// Shows the colour of the next piece on the spare top row buttons, between the arrows and exit
+fn show_next<D: GridDisplay>(display: &mut D, next: &tetris::Piece) -> Result<(), D::Error> {
+    for n in 4..7 {
+        display.send_button(Button::Top(n), next.color)?;
+    }
+    Ok(())
+}
+
+/// Colour of the arrows that point at parts of the well out of view
+const OFFSCREEN_COLOR: u8 = palette::ORANGE;
+
+/// Lights the arrow buttons that point at anything out of view
+fn show_offscreen<D: GridDisplay>(display: &mut D, offscreen: Offscreen) -> Result<(), D::Error> {
+    let arrows = [
+        offscreen.above,
+        offscreen.below,
+        offscreen.left,
+        offscreen.right,
+    ];
+    for (n, &lit) in arrows.iter().enumerate() {
+        let color = if lit { OFFSCREEN_COLOR } else { palette::OFF };
+        display.send_button(Button::Top(n as u8), color)?;
+    }
+    Ok(())
+}
+
+/// Returns where the piece is, for the viewport to follow,
+/// and how high the stack is underneath it
+fn piece_focus(board: &tetris::Board, piece: &tetris::Piece, x: usize, y: usize) -> (Rect, usize) {
+    let render = piece.render();
+    let rect = Rect {
+        x,
+        y,
+        width: render.num_columns(),
+        height: render.num_rows(),
+    };
+    let stack = (x..x + rect.width)
+        .map(|ix| usize::from(board.column_height(ix)))
+        .max()
+        .unwrap_or(0);
+    (rect, stack)
+}
+
+/// Shows the score, level, lines and next piece on displays that have room for them
+fn show_status<D: GridDisplay>(
+    display: &mut D,
+    score: u32,
+    level: u32,
+    lines: u32,
+    next: &tetris::Piece,
+) -> Result<(), D::Error> {
+    // The piece renders top to bottom, frames go bottom to top
+    let render = next.render();
+    let mut preview = Array2D::filled_with(0, render.num_rows(), render.num_columns());
+    for y in 0..render.num_rows() {
+        for x in 0..render.num_columns() {
+            if render[(render.num_rows() - y - 1, x)] {
+                preview[(y, x)] = next.color;
+            }
+        }
+    }
+    display.show_status(&Status {
+        score,
+        level,
+        lines,
+        next: preview,
+    })
+}
+
+/// Returns the speed a level starts at
+fn level_speed(level: u32) -> u8 {
+    (1 + (level - 1) * 24).min(240) as u8
+}
+
+/// Returns the auto repeat for held moves, with the delay and interval in milliseconds
+/// taken from LP_TETRIS_DAS and LP_TETRIS_ARR if they're set
+fn auto_repeat() -> AutoRepeat {
+    let default = AutoRepeat::default();
+    let ms = |name| {
+        env::var(name)
+            .ok()
+            .and_then(|ms| ms.parse().ok())
+            .map(Duration::from_millis)
+    };
+    match (ms("LP_TETRIS_DAS"), ms("LP_TETRIS_ARR")) {
+        (None, None) => default,
+        (delay, interval) => AutoRepeat::new(
+            delay.unwrap_or(default.delay()),
+            interval.unwrap_or(default.interval()),
+        ),
+    }
+}
+
+/// Runs a game by `rules` until it's lost or the player quits.
+/// Frames go to `display`, through a viewport if the well doesn't fit,
+/// and controls come from `poll_input`, which is emptied every frame
+/// and applied in the order things happened. Held moves repeat with `repeat`.
+/// Stops early if the display fails.
+fn gameloop<D: GridDisplay>(
+    display: &mut D,
+    mut rules: Rules,
+    mut repeat: AutoRepeat,
+    poll_input: impl Fn(&mut D) -> Option<InputEvent>,
+) -> Result<Outcome, D::Error> {
+    let mut current_piece = tetris::Piece::new(rules.randomizer.next());
+    let mut next_piece = tetris::Piece::new(rules.randomizer.next());
+    // The well is as big as the display unless the rules say otherwise
+    let well = rules.well.unwrap_or(display.size());
+    let (width, height) = well;
+    let mut board = tetris::Board::with_size(width, height);
+    let (view_width, view_height) = display.size();
+    let mut viewport = Viewport::new(view_width, view_height);
+    let mut offscreen = Offscreen::default();
+    // Pieces spawn in the middle, in the hidden rows just above the top, and drop straight into view
+    let spawn_x = (width / 2).saturating_sub(1);
+    let spawn_y = height;
+    let mut pos_x: usize = spawn_x;
+    let mut pos_y: usize = spawn_y - 1;
     let mut drop_down: bool = false;
-    let mut speed: u8 = 1;
+    let mut level: u32 = 1;
+    let mut lines: u32 = 0;
+    let mut score: u32 = 0;
+    let mut speed: u8 = level_speed(level);
     let mut tick: u32 = 0;
-    let mut board = tetris::Board::new();
-    let mut lp = Launchpad::new();
-    println!("Connection open!!");
-    lp.clear();
+    let mut paused = false;
+    show_level(display, level)?;
+    show_next(display, &next_piece)?;
+    show_status(display, score, level, lines, &next_piece)?;
     'gameloop: loop {
         sleep(Duration::from_millis(4));
+        // Nothing can be seen while the display is gone, so wait for it
+        if paused {
+            match poll_input(display)
+                .filter(|input| input.pressed)
+                .map(|input| input.event)
+            {
+                Some(ControlEvent::Reconnected) => {
+                    println!("Display reconnected");
+                    paused = false;
+                    show_level(display, level)?;
+                    show_offscreen(display, offscreen)?;
+                    show_next(display, &next_piece)?;
+                    show_status(display, score, level, lines, &next_piece)?;
+                }
+                Some(ControlEvent::ExitGame) => break 'gameloop,
+                _ => (),
+            }
+            continue;
+        }
+        // Hold everything while a message is showing, anything drawn now would cover it
+        if display.is_scrolling() {
+            match poll_input(display)
+                .filter(|input| input.pressed)
+                .map(|input| input.event)
+            {
+                Some(ControlEvent::ExitGame) => break 'gameloop,
+                Some(ControlEvent::Disconnected) => {
+                    println!("Display disconnected, paused until it's back");
+                    paused = true;
+                    repeat.clear();
+                }
+                _ => (),
+            }
+            continue;
+        }
         tick += 1;
-        lp.send_matrix(board.shadow(&current_piece, pos_x, pos_y));
+        let frame = board.shadow(&current_piece, pos_x, pos_y);
+        let (focus, stack) = piece_focus(&board, &current_piece, pos_x, pos_y);
+        viewport.follow(well, focus, stack, Instant::now());
+        display.send_matrix(viewport.crop(&frame))?;
+        if viewport.offscreen(&frame) != offscreen {
+            offscreen = viewport.offscreen(&frame);
+            show_offscreen(display, offscreen)?;
+        }
         let tickrate = match 255u8.checked_sub(speed) {
             Some(tr) => tr,
             None => panic!(
@@ -63,54 +643,101 @@ fn main() {
                 speed
             ),
         };
-        if tick % tickrate as u32 == 0 || drop_down {
-            if pos_y == 0 {
-                board.place(&current_piece, pos_x, pos_y);
-                current_piece = tetris::Piece::new(rng.gen());
-                pos_y = 7;
-                pos_x = 3;
-                drop_down = false;
+        if tick.is_multiple_of(tickrate as u32) || drop_down {
+            if pos_y > 0
+                && board.collides(&current_piece, pos_x, pos_y - 1) == CollisionResult::Unobstructed
+            {
+                pos_y -= 1;
             } else {
-                match board.collides(&current_piece, pos_x, pos_y.saturating_sub(1)) {
-                    // CollisionResult::AboveRoof => {
-                    //     pos_y = pos_y.saturating_sub(1);
-                    // },
-                    CollisionResult::Unobstructed => {
-                        pos_y = pos_y.saturating_sub(1);
+                // Landed, lock it in
+                // A piece that won't fit on the board can't lock either, that ends the game too
+                let locked_out = board.locked_out(&current_piece, pos_y)
+                    || board.place(&current_piece, pos_x, pos_y).is_err();
+                let cleared = board.clear_rows();
+                if cleared > 0 {
+                    lines += u32::from(cleared);
+                    score += LINE_SCORES[usize::from(cleared).min(4)] * level;
+                    let leveled_up = 1 + lines / LINES_PER_LEVEL > level;
+                    if leveled_up {
+                        level = 1 + lines / LINES_PER_LEVEL;
+                        speed = level_speed(level);
+                        show_level(display, level)?;
                     }
-                    _ => {
-                        board.place(&current_piece, pos_x, pos_y);
-                        current_piece = tetris::Piece::new(rng.gen());
-                        pos_y = 7;
-                        pos_x = 3;
-                        drop_down = false;
+                    show_status(display, score, level, lines, &next_piece)?;
+                    if leveled_up {
+                        display.scroll_text(
+                            &format!("LEVEL {}", level),
+                            palette::GREEN,
+                            6,
+                            false,
+                        )?;
+                        // Releases aren't seen while it scrolls
+                        repeat.clear();
                     }
                 }
+                current_piece =
+                    std::mem::replace(&mut next_piece, tetris::Piece::new(rules.randomizer.next()));
+                show_next(display, &next_piece)?;
+                show_status(display, score, level, lines, &next_piece)?;
+                pos_y = spawn_y;
+                pos_x = spawn_x;
+                drop_down = false;
+                // Locked entirely out of sight, or nowhere for the next one to go
+                if locked_out || board.blocked_out(&current_piece, pos_x, pos_y) {
+                    return Ok(Outcome {
+                        final_frame: Some(viewport.crop(&board.shadow(
+                            &current_piece,
+                            pos_x,
+                            pos_y,
+                        ))),
+                        score,
+                    });
+                }
+                if board.collides(&current_piece, pos_x, pos_y - 1) == CollisionResult::Unobstructed
+                {
+                    pos_y -= 1;
+                }
             }
-            board.clear_rows();
-            if board.finished() {
-                break 'gameloop;
+        }
+        // Everything that's come in since the last frame, oldest first, then any held moves
+        let now = Instant::now();
+        let mut inputs: Vec<InputEvent> = iter::from_fn(|| poll_input(display)).collect();
+        inputs.sort_by_key(|input| input.time);
+        let mut events = Vec::new();
+        for input in inputs {
+            repeat.input(input);
+            if input.pressed {
+                events.push(input.event);
             }
         }
-        if let Some(event) = lp.poll_input() {
+        events.extend(iter::from_fn(|| repeat.poll(now)));
+        for event in events {
+            if paused {
+                break;
+            }
             match event {
-                ControlEvent::MoveLeft => {
-                    match board.collides(&current_piece, pos_x.saturating_sub(1), pos_y) {
-                        CollisionResult::Unobstructed => pos_x = pos_x.saturating_sub(1),
-                        // CollisionResult::AboveRoof => pos_x = pos_x.saturating_sub(1),
-                        _ => (),
-                    }
+                ControlEvent::MoveLeft
+                    if board.collides(&current_piece, pos_x.saturating_sub(1), pos_y)
+                        == CollisionResult::Unobstructed =>
+                {
+                    pos_x = pos_x.saturating_sub(1);
                 }
-                ControlEvent::MoveRight => {
-                    match board.collides(&current_piece, pos_x.saturating_add(1), pos_y) {
-                        CollisionResult::Unobstructed => pos_x = pos_x.saturating_add(1),
-                        // CollisionResult::AboveRoof => pos_x = pos_x.saturating_add(1),
-                        _ => (),
-                    }
+                ControlEvent::MoveRight
+                    if board.collides(&current_piece, pos_x.saturating_add(1), pos_y)
+                        == CollisionResult::Unobstructed =>
+                {
+                    pos_x = pos_x.saturating_add(1);
                 }
                 ControlEvent::RotateLeft => {
+                    let from = current_piece.rotation();
                     current_piece.rotate_left();
-                    if let Some((new_x, new_y)) = board.try_rotation(&current_piece, pos_x, pos_y) {
+                    if let Some((new_x, new_y)) = board.try_rotation(
+                        rules.rotation.as_ref(),
+                        &current_piece,
+                        from,
+                        pos_x,
+                        pos_y,
+                    ) {
                         pos_x = new_x;
                         pos_y = new_y;
                     } else {
@@ -118,8 +745,15 @@ fn main() {
                     }
                 }
                 ControlEvent::RotateRight => {
+                    let from = current_piece.rotation();
                     current_piece.rotate_right();
-                    if let Some((new_x, new_y)) = board.try_rotation(&current_piece, pos_x, pos_y) {
+                    if let Some((new_x, new_y)) = board.try_rotation(
+                        rules.rotation.as_ref(),
+                        &current_piece,
+                        from,
+                        pos_x,
+                        pos_y,
+                    ) {
                         pos_x = new_x;
                         pos_y = new_y;
                     } else {
@@ -131,8 +765,20 @@ fn main() {
                 }
                 ControlEvent::SpeedChange(s) => speed = s,
                 ControlEvent::ExitGame => break 'gameloop,
+                ControlEvent::Disconnected => {
+                    println!("Display disconnected, paused until it's back");
+                    paused = true;
+                    repeat.clear();
+                }
+                // Soft drop, landing is left to gravity
+                ControlEvent::MoveDown
+                    if pos_y > 0
+                        && board.collides(&current_piece, pos_x, pos_y - 1)
+                            == CollisionResult::Unobstructed =>
+                {
+                    pos_y -= 1;
+                }
                 // ControlEvent::MoveUp => pos_y = pos_y.saturating_add(1),
-                // ControlEvent::MoveDown => pos_y = pos_y.saturating_sub(1)
                 _ => (),
             }
         }
@@ -154,4 +800,8 @@ fn main() {
     // //     }
     // // }
     // lp.close();
+    Ok(Outcome {
+        final_frame: None,
+        score,
+    })
 }
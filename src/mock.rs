@@ -0,0 +1,562 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! An in-memory stand-in for a Launchpad's MIDI output.
+//! Records every message sent to it and can decode them back into pad updates,
+//! so rendering can be tested without any hardware attached.
+
+use crate::{Button, Color, LaunchpadError, LaunchpadModel, MidiSink};
+use array2d::Array2D;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// The part of the SysEx header shared by every model, the device ID comes after it
+const HEADER: [u8; 5] = [0xF0, 0x00, 0x20, 0x29, 0x02];
+
+/// A decoded Launchpad SysEx message.
+/// LEDs that aren't buttons, like the logo on newer models, are left out.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Update {
+    /// Set LEDs (0x0A, or static colours with 0x03), as (button, colour) pairs
+    Leds(Vec<(Button, u8)>),
+    /// Set LEDs to RGB colours (0x0B, or RGB with 0x03), channels scaled to 0-63
+    Rgb(Vec<(Button, (u8, u8, u8))>),
+    /// Flash LEDs (0x23, or flashing with 0x03) between a colour and off
+    Flash(Vec<(Button, u8)>),
+    /// Pulse LEDs (0x28, or pulsing with 0x03)
+    Pulse(Vec<(Button, u8)>),
+    /// Set all LEDs (0x0E on the MK2 and Pro) to a single colour
+    All(u8),
+    /// Scroll text (0x14, or 0x07 on newer models). Empty text stops scrolling
+    Scroll {
+        text: String,
+        velocity: u8,
+        speed: u8,
+        looping: bool,
+    },
+}
+
+/// A MidiSink that keeps a copy of every message sent to it.
+/// Clones share the same log, so keep one around to inspect
+/// after handing the other to Launchpad::with_output.
+#[derive(Clone, Default, Debug)]
+pub struct MockOutput {
+    log: Arc<Mutex<Vec<Vec<u8>>>>,
+    /// Set to make every send fail, as if the device was unplugged
+    unplugged: Arc<AtomicBool>,
+}
+
+impl MockOutput {
+    /// Returns a new mock with an empty log
+    pub fn new() -> MockOutput {
+        MockOutput::default()
+    }
+    /// Returns every raw message sent so far, oldest first
+    pub fn messages(&self) -> Vec<Vec<u8>> {
+        self.log.lock().unwrap().clone()
+    }
+    /// Empties the log
+    pub fn reset(&self) {
+        self.log.lock().unwrap().clear();
+    }
+    /// Makes every send fail with SendFailed until plug is called.
+    /// Nothing is logged while unplugged.
+    pub fn unplug(&self) {
+        self.unplugged.store(true, Ordering::SeqCst);
+    }
+    /// Undoes unplug
+    pub fn plug(&self) {
+        self.unplugged.store(false, Ordering::SeqCst);
+    }
+    /// Returns every message sent so far, decoded.
+    /// Panics if anything in the log isn't a message the mock understands.
+    pub fn updates(&self) -> Vec<Update> {
+        self.messages()
+            .iter()
+            .map(|msg| decode(msg).unwrap_or_else(|| panic!("Undecodable message {:02X?}", msg)))
+            .collect()
+    }
+    /// Replays the log onto a blank grid and returns what the device would be showing.
+    /// Indexed the same way as the matrix passed to send_color_matrix.
+    /// Flashing and pulsing pads show as the colour they're flashing or pulsing.
+    pub fn color_state(&self) -> Array2D<Color> {
+        let mut grid = Array2D::filled_with(Color::OFF, 8, 8);
+        for (button, color) in self.colors() {
+            match button {
+                None => grid = Array2D::filled_with(color, 8, 8),
+                Some(Button::Grid(pad)) => {
+                    grid.set(pad.y as usize, pad.x as usize, color).ok();
+                }
+                Some(_) => (),
+            }
+        }
+        grid
+    }
+    /// Same as color_state, but for a single button, which can be a round one
+    pub fn button_color(&self, button: Button) -> Color {
+        self.colors()
+            .into_iter()
+            .rev()
+            .find(|(which, _)| which.is_none() || *which == Some(button))
+            .map_or(Color::OFF, |(_, color)| color)
+    }
+    /// Every colour set so far, oldest first. A button of None means all of them
+    fn colors(&self) -> Vec<(Option<Button>, Color)> {
+        let mut colors = Vec::new();
+        for update in self.updates() {
+            match update {
+                Update::All(color) => colors.push((None, Color::Palette(color))),
+                Update::Leds(leds) | Update::Flash(leds) | Update::Pulse(leds) => colors.extend(
+                    leds.into_iter()
+                        .map(|(button, color)| (Some(button), Color::Palette(color))),
+                ),
+                Update::Scroll { .. } => (),
+                Update::Rgb(leds) => colors.extend(
+                    leds.into_iter()
+                        .map(|(button, (r, g, b))| (Some(button), Color::Rgb(r, g, b))),
+                ),
+            }
+        }
+        colors
+    }
+    /// Same as color_state, but as palette indexes like the matrix passed to send_matrix.
+    /// Panics if any pad is showing an RGB colour.
+    pub fn state(&self) -> Array2D<u8> {
+        let colors = self.color_state();
+        let indexes: Vec<u8> = colors
+            .elements_row_major_iter()
+            .map(|color| match color {
+                Color::Palette(index) => *index,
+                rgb => panic!("Pad is showing {:?}, use color_state", rgb),
+            })
+            .collect();
+        Array2D::from_row_major(&indexes, 8, 8)
+    }
+}
+
+impl MidiSink for MockOutput {
+    fn send(&mut self, msg: &[u8]) -> Result<(), LaunchpadError> {
+        if self.unplugged.load(Ordering::SeqCst) {
+            return Err(LaunchpadError::SendFailed("unplugged".into()));
+        }
+        self.log.lock().unwrap().push(msg.to_vec());
+        Ok(())
+    }
+}
+
+/// Decodes a single raw Launchpad SysEx message, for whichever model its header says.
+/// Returns None if it isn't one of the messages listed in Update.
+/// ```
+/// # use lp_tetris::{mock::{decode, Update}, Button, Pad};
+/// let msg = [0xF0, 0x00, 0x20, 0x29, 0x02, 0x18, 0x0A, 11, 5, 0xF7];
+/// assert_eq!(decode(&msg), Some(Update::Leds(vec![(Button::Grid(Pad { x: 0, y: 0 }), 5)])));
+/// let msg = [0xF0, 0x00, 0x20, 0x29, 0x02, 0x0D, 0x03, 0, 11, 5, 0xF7];
+/// assert_eq!(decode(&msg), Some(Update::Leds(vec![(Button::Grid(Pad { x: 0, y: 0 }), 5)])));
+/// ```
+pub fn decode(msg: &[u8]) -> Option<Update> {
+    if msg.len() < HEADER.len() + 3 || !msg.starts_with(&HEADER) || msg.last() != Some(&0xF7) {
+        return None;
+    }
+    let model = LaunchpadModel::from_device_id(msg[HEADER.len()])?;
+    let msg_type = msg[HEADER.len() + 1];
+    let data = &msg[HEADER.len() + 2..msg.len() - 1];
+    let button = |index: u8| Button::from_index(model, index);
+    match (model.lighting_spec(), msg_type, data) {
+        (false, 0x0E, [color]) => Some(Update::All(*color)),
+        (false, 0x0A, leds) if leds.len() % 2 == 0 => Some(Update::Leds(
+            leds.chunks(2)
+                .filter_map(|led| Some((button(led[0])?, led[1])))
+                .collect(),
+        )),
+        (false, 0x0B, leds) if leds.len() % 4 == 0 => Some(Update::Rgb(
+            leds.chunks(4)
+                .filter_map(|led| Some((button(led[0])?, (led[1], led[2], led[3]))))
+                .collect(),
+        )),
+        (false, 0x23, leds) if leds.len() % 2 == 0 => Some(Update::Flash(
+            leds.chunks(2)
+                .filter_map(|led| Some((button(led[0])?, led[1])))
+                .collect(),
+        )),
+        (false, 0x28, leds) if leds.len() % 2 == 0 => Some(Update::Pulse(
+            leds.chunks(2)
+                .filter_map(|led| Some((button(led[0])?, led[1])))
+                .collect(),
+        )),
+        (false, 0x14, [velocity, looping, text @ ..]) => {
+            // A leading byte of 1-7 sets the speed, otherwise it defaults to 4
+            let (speed, text) = match text {
+                [speed @ 1..=7, text @ ..] => (*speed, text),
+                text => (4, text),
+            };
+            Some(Update::Scroll {
+                text: String::from_utf8_lossy(text).into_owned(),
+                velocity: *velocity,
+                speed,
+                looping: *looping != 0,
+            })
+        }
+        (true, 0x07, [looping, speed, 0, velocity, text @ ..]) => Some(Update::Scroll {
+            text: String::from_utf8_lossy(text).into_owned(),
+            velocity: *velocity,
+            speed: *speed / 4,
+            looping: *looping != 0,
+        }),
+        // Each lighting spec is a type, an LED index, and data depending on the type.
+        // A single message can only be decoded if every spec is the same type.
+        (true, 0x03, specs) => {
+            let scale = |c: u8| ((u16::from(c) * 63 + 63) / 127) as u8;
+            let mut updates = Vec::new();
+            let mut rest = specs;
+            while !rest.is_empty() {
+                let (update, tail) = match rest {
+                    [0, i, c, tail @ ..] => (button(*i).map(|b| Update::Leds(vec![(b, *c)])), tail),
+                    // Flashes from colour B to colour A, B is assumed to be off
+                    [1, i, _, c, tail @ ..] => {
+                        (button(*i).map(|b| Update::Flash(vec![(b, *c)])), tail)
+                    }
+                    [2, i, c, tail @ ..] => {
+                        (button(*i).map(|b| Update::Pulse(vec![(b, *c)])), tail)
+                    }
+                    [3, i, r, g, b, tail @ ..] => (
+                        button(*i).map(|button| {
+                            Update::Rgb(vec![(button, (scale(*r), scale(*g), scale(*b)))])
+                        }),
+                        tail,
+                    ),
+                    _ => return None,
+                };
+                updates.extend(update);
+                rest = tail;
+            }
+            merge(updates)
+        }
+        _ => None,
+    }
+}
+
+/// Joins updates of the same kind into one, or returns None if they're different kinds
+fn merge(updates: Vec<Update>) -> Option<Update> {
+    let mut updates = updates.into_iter();
+    let mut merged = updates.next()?;
+    for update in updates {
+        match (&mut merged, update) {
+            (Update::Leds(all), Update::Leds(more))
+            | (Update::Flash(all), Update::Flash(more))
+            | (Update::Pulse(all), Update::Pulse(more)) => all.extend(more),
+            (Update::Rgb(all), Update::Rgb(more)) => all.extend(more),
+            _ => return None,
+        }
+    }
+    Some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockOutput, Update};
+    use crate::{
+        Button, Color, ControlEvent, GridDisplay, Launchpad, LaunchpadError, LaunchpadModel, Pad,
+    };
+    use array2d::Array2D;
+
+    #[test]
+    fn clear() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.clear().unwrap();
+        assert_eq!(out.updates(), vec![Update::All(0)]);
+    }
+    #[test]
+    fn single_pad() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.send_pad(Pad { x: 2, y: 6 }, 21).unwrap();
+        assert_eq!(
+            out.messages(),
+            vec![vec![0xF0, 0x00, 0x20, 0x29, 0x02, 0x18, 0x0A, 73, 21, 0xF7]]
+        );
+    }
+    #[test]
+    fn matrix_frame() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        let vec: Vec<u8> = (0..64).collect();
+        let matrix = Array2D::from_row_major(&vec, 8, 8);
+        lp.send_matrix(matrix.clone()).unwrap();
+        match &out.updates()[..] {
+            [Update::Leds(leds)] => {
+                assert_eq!(leds.len(), 64);
+                assert_eq!(leds[0], (Button::Grid(Pad { x: 0, y: 0 }), 0));
+                assert_eq!(leds[9], (Button::Grid(Pad { x: 1, y: 1 }), 9));
+                assert_eq!(leds[63], (Button::Grid(Pad { x: 7, y: 7 }), 63));
+            }
+            other => panic!("Expected a single Leds update, got {:?}", other),
+        }
+        assert_eq!(out.state(), matrix);
+    }
+    #[test]
+    fn oversized_matrix() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        let vec: Vec<u8> = (0..120).collect();
+        lp.send_matrix(Array2D::from_row_major(&vec, 10, 12))
+            .unwrap();
+        match &out.updates()[..] {
+            [Update::Leds(leds)] => assert_eq!(leds.len(), 64),
+            other => panic!("Expected a single Leds update, got {:?}", other),
+        }
+        let cropped: Vec<u8> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| y * 12 + x))
+            .collect();
+        assert_eq!(out.state(), Array2D::from_row_major(&cropped, 8, 8));
+    }
+    #[test]
+    fn clear_after_frame() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.send_matrix(Array2D::filled_with(5, 8, 8)).unwrap();
+        lp.clear().unwrap();
+        assert_eq!(out.state(), Array2D::filled_with(0, 8, 8));
+        out.reset();
+        assert!(out.messages().is_empty());
+    }
+    #[test]
+    fn other_models() {
+        for model in LaunchpadModel::ALL.iter() {
+            let out = MockOutput::new();
+            let mut lp = Launchpad::with_output_model(out.clone(), *model);
+            let vec: Vec<u8> = (0..64).collect();
+            let matrix = Array2D::from_row_major(&vec, 8, 8);
+            lp.send_matrix(matrix.clone()).unwrap();
+            assert_eq!(out.messages()[0][5], model.device_id());
+            assert_eq!(out.state(), matrix);
+            lp.clear().unwrap();
+            assert_eq!(out.state(), Array2D::filled_with(0, 8, 8));
+        }
+    }
+    #[test]
+    fn rgb_frame() {
+        for model in LaunchpadModel::ALL.iter() {
+            let out = MockOutput::new();
+            let mut lp = Launchpad::with_output_model(out.clone(), *model);
+            let mut matrix = Array2D::filled_with(Color::Palette(5), 8, 8);
+            matrix.set(0, 0, Color::Rgb(63, 1, 32)).unwrap();
+            matrix.set(7, 3, Color::Rgb(0, 40, 0)).unwrap();
+            lp.send_color_matrix(matrix.clone()).unwrap();
+            assert_eq!(out.messages().len(), 2);
+            assert_eq!(out.color_state(), matrix);
+            lp.send_pad_color(Pad { x: 0, y: 0 }, Color::Palette(9))
+                .unwrap();
+            matrix.set(0, 0, Color::Palette(9)).unwrap();
+            assert_eq!(out.color_state(), matrix);
+        }
+    }
+    #[test]
+    fn small_color_matrix() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.send_color_matrix(Array2D::filled_with(Color::Palette(5), 2, 3))
+            .unwrap();
+        let mut expected = Array2D::filled_with(Color::OFF, 8, 8);
+        for y in 0..2 {
+            for x in 0..3 {
+                expected.set(y, x, Color::Palette(5)).unwrap();
+            }
+        }
+        assert_eq!(out.color_state(), expected);
+    }
+    #[test]
+    fn flash_and_pulse() {
+        for model in LaunchpadModel::ALL.iter() {
+            let out = MockOutput::new();
+            let mut lp = Launchpad::with_output_model(out.clone(), *model);
+            lp.flash_pad(Pad { x: 1, y: 2 }, 5).unwrap();
+            lp.pulse_pad(Pad { x: 3, y: 4 }, 9).unwrap();
+            let mut matrix = Array2D::filled_with(0, 8, 8);
+            matrix.set(0, 0, 13).unwrap();
+            matrix.set(7, 7, 21).unwrap();
+            lp.pulse_matrix(matrix.clone()).unwrap();
+            lp.flash_matrix(Array2D::filled_with(0, 8, 8)).unwrap();
+            assert_eq!(
+                out.updates(),
+                vec![
+                    Update::Flash(vec![(Button::Grid(Pad { x: 1, y: 2 }), 5)]),
+                    Update::Pulse(vec![(Button::Grid(Pad { x: 3, y: 4 }), 9)]),
+                    Update::Pulse(vec![
+                        (Button::Grid(Pad { x: 0, y: 0 }), 13),
+                        (Button::Grid(Pad { x: 7, y: 7 }), 21)
+                    ]),
+                ]
+            );
+        }
+    }
+    #[test]
+    fn pulse_off_the_grid() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        let pad = Pad { x: 8, y: 0 };
+        assert_eq!(
+            lp.flash_pad(pad, 5),
+            Err(LaunchpadError::NoSuchButton(Button::Grid(pad)))
+        );
+        let mut matrix = Array2D::filled_with(0, 3, 10);
+        matrix.set(2, 9, 13).unwrap();
+        matrix.set(1, 1, 21).unwrap();
+        lp.pulse_matrix(matrix).unwrap();
+        assert_eq!(
+            out.updates(),
+            vec![Update::Pulse(vec![(Button::Grid(Pad { x: 1, y: 1 }), 21)])]
+        );
+    }
+    #[test]
+    fn scroll_text() {
+        for model in LaunchpadModel::ALL.iter() {
+            let out = MockOutput::new();
+            let mut lp = Launchpad::with_output_model(out.clone(), *model);
+            lp.scroll_text("SCORE 1200", 13, 7, true).unwrap();
+            lp.stop_scroll().unwrap();
+            assert_eq!(
+                out.updates(),
+                vec![
+                    Update::Scroll {
+                        text: "SCORE 1200".into(),
+                        velocity: 13,
+                        speed: 7,
+                        looping: true
+                    },
+                    Update::Scroll {
+                        text: "".into(),
+                        velocity: 0,
+                        speed: 1,
+                        looping: false
+                    },
+                ]
+            );
+        }
+    }
+    #[test]
+    fn only_changes_sent() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        let mut matrix = Array2D::filled_with(0, 8, 8);
+        lp.send_matrix(matrix.clone()).unwrap();
+        out.reset();
+        lp.send_matrix(matrix.clone()).unwrap();
+        assert!(out.messages().is_empty());
+        matrix.set(1, 2, 5).unwrap();
+        matrix.set(6, 7, 9).unwrap();
+        lp.send_matrix(matrix.clone()).unwrap();
+        assert_eq!(
+            out.updates(),
+            vec![Update::Leds(vec![
+                (Button::Grid(Pad { x: 2, y: 1 }), 5),
+                (Button::Grid(Pad { x: 7, y: 6 }), 9)
+            ])]
+        );
+        out.reset();
+        lp.send_pad(Pad { x: 2, y: 1 }, 0).unwrap();
+        matrix.set(1, 2, 0).unwrap();
+        lp.send_matrix(matrix.clone()).unwrap();
+        assert_eq!(out.messages().len(), 1);
+        out.reset();
+        lp.refresh().unwrap();
+        match &out.updates()[..] {
+            [Update::Leds(leds)] => assert_eq!(leds.len(), 64),
+            other => panic!("Expected a full frame, got {:?}", other),
+        }
+        assert_eq!(out.state(), matrix);
+    }
+    #[test]
+    fn full_frame_after_clear() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.send_matrix(Array2D::filled_with(5, 8, 8)).unwrap();
+        lp.clear().unwrap();
+        out.reset();
+        lp.send_matrix(Array2D::filled_with(5, 8, 8)).unwrap();
+        assert_eq!(out.state(), Array2D::filled_with(5, 8, 8));
+    }
+    #[test]
+    fn send_failure() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.send_matrix(Array2D::filled_with(5, 8, 8)).unwrap();
+        out.unplug();
+        assert_eq!(
+            lp.send_matrix(Array2D::filled_with(9, 8, 8)),
+            Err(LaunchpadError::SendFailed("unplugged".into()))
+        );
+        out.plug();
+        out.reset();
+        // The failed frame might not have arrived, so all of it is sent again
+        lp.send_matrix(Array2D::filled_with(9, 8, 8)).unwrap();
+        assert_eq!(out.state(), Array2D::filled_with(9, 8, 8));
+    }
+    #[test]
+    fn reconnect() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.set_reconnect(true);
+        let mut matrix = Array2D::filled_with(0, 8, 8);
+        matrix.set(3, 4, 5).unwrap();
+        lp.send_matrix(matrix.clone()).unwrap();
+        out.unplug();
+        out.reset();
+        // Dropped rather than failing, and remembered for when it's back
+        matrix.set(5, 6, 9).unwrap();
+        lp.send_matrix(matrix.clone()).unwrap();
+        assert!(!lp.is_connected());
+        assert_eq!(lp.poll_input(), Some(ControlEvent::Disconnected));
+        assert_eq!(lp.poll_input(), None);
+        out.plug();
+        lp.check_connection();
+        assert_eq!(lp.poll_input(), Some(ControlEvent::Reconnected));
+        assert!(lp.is_connected());
+        assert_eq!(out.state(), matrix);
+    }
+    #[test]
+    fn round_buttons() {
+        for model in LaunchpadModel::ALL.iter() {
+            let out = MockOutput::new();
+            let mut lp = Launchpad::with_output_model(out.clone(), *model);
+            lp.send_button(Button::Top(7), 5).unwrap();
+            lp.send_button(Button::Scene(0), 9).unwrap();
+            lp.send_button(Button::Grid(Pad { x: 3, y: 3 }), 13)
+                .unwrap();
+            assert_eq!(out.button_color(Button::Top(7)), Color::Palette(5));
+            assert_eq!(out.button_color(Button::Scene(0)), Color::Palette(9));
+            assert_eq!(out.button_color(Button::Top(0)), Color::OFF);
+            assert_eq!(out.state()[(3, 3)], 13);
+            lp.clear().unwrap();
+            assert_eq!(out.button_color(Button::Scene(0)), Color::OFF);
+        }
+    }
+    #[test]
+    fn out_of_range_buttons() {
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        for button in [Button::Top(8), Button::Top(255), Button::Scene(30)].iter() {
+            assert_eq!(
+                lp.send_button(*button, 5),
+                Err(LaunchpadError::NoSuchButton(*button))
+            );
+        }
+        assert!(out.messages().is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! The different Launchpads we know how to talk to.
+//! They all share Novation's SysEx framing and (in programmer mode) the 11-88 grid layout,
+//! but differ in device ID, LED commands and how the round buttons are numbered.
+
+use std::time::Duration;
+
+/// A supported Launchpad model
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LaunchpadModel {
+    Mk2,
+    Pro,
+    ProMk3,
+    X,
+    MiniMk3,
+}
+
+impl LaunchpadModel {
+    /// Every supported model
+    pub const ALL: [LaunchpadModel; 5] = [
+        LaunchpadModel::Mk2,
+        LaunchpadModel::Pro,
+        LaunchpadModel::ProMk3,
+        LaunchpadModel::X,
+        LaunchpadModel::MiniMk3,
+    ];
+    /// Works out the model from a MIDI port name.
+    /// Returns None for ports that aren't Launchpads,
+    /// and for the DAW ports of newer models, which don't take programmer mode messages.
+    /// ```
+    /// # use lp_tetris::LaunchpadModel;
+    /// assert_eq!(LaunchpadModel::from_port_name("Launchpad MK2"), Some(LaunchpadModel::Mk2));
+    /// assert_eq!(LaunchpadModel::from_port_name("Launchpad Pro MK3:Launchpad Pro MK3 LPProMK3 MIDI 24:0"), Some(LaunchpadModel::ProMk3));
+    /// assert_eq!(LaunchpadModel::from_port_name("LPX MIDI"), Some(LaunchpadModel::X));
+    /// assert_eq!(LaunchpadModel::from_port_name("Launchpad X:Launchpad X LPX DAW In 20:0"), None);
+    /// assert_eq!(LaunchpadModel::from_port_name("Midi Through Port-0"), None);
+    /// ```
+    pub fn from_port_name(name: &str) -> Option<LaunchpadModel> {
+        if name.contains("DAW") || name.starts_with("MIDIIN2") {
+            return None;
+        }
+        // Order matters, "Launchpad Pro MK3" also contains "Launchpad Pro"
+        if name.contains("Launchpad MK2") {
+            Some(LaunchpadModel::Mk2)
+        } else if name.contains("LPProMK3") || name.contains("Launchpad Pro MK3") {
+            Some(LaunchpadModel::ProMk3)
+        } else if name.contains("Launchpad Pro") {
+            Some(LaunchpadModel::Pro)
+        } else if name.contains("LPMiniMK3") || name.contains("Launchpad Mini MK3") {
+            Some(LaunchpadModel::MiniMk3)
+        } else if name.contains("LPX") || name.contains("Launchpad X") {
+            Some(LaunchpadModel::X)
+        } else {
+            None
+        }
+    }
+    /// Returns the model with the given SysEx device ID
+    pub fn from_device_id(id: u8) -> Option<LaunchpadModel> {
+        LaunchpadModel::ALL
+            .iter()
+            .copied()
+            .find(|model| model.device_id() == id)
+    }
+    /// The device ID byte in this model's SysEx header
+    pub fn device_id(self) -> u8 {
+        match self {
+            LaunchpadModel::Mk2 => 0x18,
+            LaunchpadModel::Pro => 0x10,
+            LaunchpadModel::ProMk3 => 0x0E,
+            LaunchpadModel::X => 0x0C,
+            LaunchpadModel::MiniMk3 => 0x0D,
+        }
+    }
+    /// The bytes every SysEx message to this model starts with
+    pub fn sysex_header(self) -> [u8; 6] {
+        [0xF0, 0x00, 0x20, 0x29, 0x02, self.device_id()]
+    }
+    /// Whether this model uses the newer LED lighting command (0x03),
+    /// rather than the MK2/Pro set LEDs (0x0A) and set all (0x0E)
+    pub(crate) fn lighting_spec(self) -> bool {
+        match self {
+            LaunchpadModel::Mk2 | LaunchpadModel::Pro => false,
+            LaunchpadModel::ProMk3 | LaunchpadModel::X | LaunchpadModel::MiniMk3 => true,
+        }
+    }
+    /// The SysEx messages, as (command, data), that put the device into a mode
+    /// where the grid is laid out as 11-88 and every LED can be set.
+    pub fn programmer_mode(self) -> Vec<(u8, Vec<u8>)> {
+        match self {
+            // Session layout
+            LaunchpadModel::Mk2 => vec![(0x22, vec![0x00])],
+            // Standalone mode, then programmer layout
+            LaunchpadModel::Pro => vec![(0x21, vec![0x01]), (0x2C, vec![0x03])],
+            LaunchpadModel::ProMk3 | LaunchpadModel::X | LaunchpadModel::MiniMk3 => {
+                vec![(0x0E, vec![0x01])]
+            }
+        }
+    }
+    /// The SysEx message, as (command, data), that sets each (LED index, palette colour) pair
+    pub fn led_message(self, leds: &[(u8, u8)]) -> (u8, Vec<u8>) {
+        if self.lighting_spec() {
+            // Lighting type 0 is a static palette colour
+            (
+                0x03,
+                leds.iter().flat_map(|(i, c)| vec![0, *i, *c]).collect(),
+            )
+        } else {
+            (0x0A, leds.iter().flat_map(|(i, c)| vec![*i, *c]).collect())
+        }
+    }
+    /// The SysEx message, as (command, data), that sets each (LED index, red, green, blue).
+    /// Channels are 0-63, and scaled up on models that take 0-127.
+    pub fn rgb_message(self, leds: &[(u8, u8, u8, u8)]) -> (u8, Vec<u8>) {
+        if self.lighting_spec() {
+            let scale = |c: u8| ((u16::from(c.min(63)) * 127 + 31) / 63) as u8;
+            // Lighting type 3 is RGB
+            (
+                0x03,
+                leds.iter()
+                    .flat_map(|(i, r, g, b)| vec![3, *i, scale(*r), scale(*g), scale(*b)])
+                    .collect(),
+            )
+        } else {
+            (
+                0x0B,
+                leds.iter()
+                    .flat_map(|(i, r, g, b)| vec![*i, *r, *g, *b])
+                    .collect(),
+            )
+        }
+    }
+    /// The SysEx message, as (command, data), that flashes each (LED index, palette colour)
+    /// between that colour and off
+    pub fn flash_message(self, leds: &[(u8, u8)]) -> (u8, Vec<u8>) {
+        if self.lighting_spec() {
+            // Lighting type 1 is flashing, from colour B (off) to colour A
+            (
+                0x03,
+                leds.iter().flat_map(|(i, c)| vec![1, *i, 0, *c]).collect(),
+            )
+        } else {
+            (0x23, leds.iter().flat_map(|(i, c)| vec![*i, *c]).collect())
+        }
+    }
+    /// The SysEx message, as (command, data), that pulses each (LED index, palette colour)
+    pub fn pulse_message(self, leds: &[(u8, u8)]) -> (u8, Vec<u8>) {
+        if self.lighting_spec() {
+            // Lighting type 2 is pulsing
+            (
+                0x03,
+                leds.iter().flat_map(|(i, c)| vec![2, *i, *c]).collect(),
+            )
+        } else {
+            (0x28, leds.iter().flat_map(|(i, c)| vec![*i, *c]).collect())
+        }
+    }
+    /// The SysEx message, as (command, data), that scrolls text across the grid.
+    /// Speed is 1 (slow) to 7 (fast). Sending an empty string stops any scrolling text.
+    pub fn scroll_message(
+        self,
+        text: &str,
+        velocity: u8,
+        speed: u8,
+        looping: bool,
+    ) -> (u8, Vec<u8>) {
+        let speed = speed.clamp(1, 7);
+        // Only printable ASCII can be shown
+        let text = text
+            .bytes()
+            .map(|c| if (0x20..0x7F).contains(&c) { c } else { b'?' });
+        if self.lighting_spec() {
+            // Loop, speed in pads per second, colourspec 0 (palette) and colour, then the text
+            let mut data = vec![looping as u8, speed * 4, 0, velocity];
+            data.extend(text);
+            (0x07, data)
+        } else {
+            // Colour and loop, then the text. Bytes 1-7 in the text set the speed
+            let mut data = vec![velocity, looping as u8, speed];
+            data.extend(text);
+            (0x14, data)
+        }
+    }
+    /// The SysEx command this model sends back when scrolling text has finished,
+    /// or None if it doesn't say.
+    pub fn scroll_done_command(self) -> Option<u8> {
+        if self.lighting_spec() {
+            None
+        } else {
+            Some(0x15)
+        }
+    }
+    /// Roughly how long the given text takes to scroll past once, for models that don't say
+    pub fn scroll_duration(self, text: &str, speed: u8) -> Duration {
+        // Characters are around 6 pads wide, and the text scrolls on from the right edge
+        let pads = text.len() as u64 * 6 + 8;
+        Duration::from_millis(pads * 1000 / u64::from(speed.clamp(1, 7) * 4))
+    }
+    /// The SysEx message, as (command, data), that turns every LED off
+    pub fn clear_message(self) -> (u8, Vec<u8>) {
+        if self.lighting_spec() {
+            // No set all command here, so address every LED in the 10x10 layout
+            let leds: Vec<(u8, u8)> = (1..10)
+                .flat_map(|y| (1..10).map(move |x| (10 * y + x, 0)))
+                .collect();
+            self.led_message(&leds)
+        } else {
+            (0x0E, vec![0])
+        }
+    }
+    /// The control change number of the leftmost round button on the top row.
+    /// The rest follow on from it.
+    pub fn top_row_cc(self) -> u8 {
+        match self {
+            LaunchpadModel::Mk2 => 104,
+            _ => 91,
+        }
+    }
+    /// Whether the right-hand column of round buttons sends notes (MK2) or control changes.
+    /// Either way they're numbered 19, 29, ... 89.
+    pub fn scene_is_note(self) -> bool {
+        self == LaunchpadModel::Mk2
+    }
+}
@@ -0,0 +1,377 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+use crate::{
+    input::Bindings, record::Recorder, Button, Color, ControlEvent, Controller, GridDisplay,
+    InputEvent, Launchpad, LaunchpadError, Pad, PortInfo,
+};
+use array2d::Array2D;
+
+/// Several launchpads tiled into one bigger grid.
+/// Each one is an 8x8 tile, laid out left to right and then bottom to top,
+/// so with 2 columns the first two launchpads are the bottom row.
+/// Every row is full, so the grid is always a rectangle.
+/// The round buttons along the top row of tiles make up Button::Top,
+/// and the ones down the right-hand column of tiles make up Button::Scene.
+pub struct MultiLaunchpad {
+    /// Each launchpad and its (column, row) in tiles
+    tiles: Vec<(Launchpad, usize, usize)>,
+    columns: usize,
+    rows: usize,
+    /// Which tiles have sent Disconnected without a Reconnected yet
+    disconnected: Vec<bool>,
+}
+
+// Core defs
+impl MultiLaunchpad {
+    /// Tiles the launchpads `columns` wide, filling rows from the bottom left.
+    /// One column of two launchpads gives an 8x16 grid, two columns of four give 16x16.
+    /// Fails with BadLayout unless the launchpads fill every row.
+    /// ```
+    /// # use lp_tetris::{mock::MockOutput, GridDisplay, Launchpad, MultiLaunchpad};
+    /// let tiles = || (0..4).map(|_| Launchpad::with_output(MockOutput::new())).collect();
+    /// assert_eq!(MultiLaunchpad::new(tiles(), 2).unwrap().size(), (16, 16));
+    /// assert!(MultiLaunchpad::new(tiles(), 3).is_err());
+    /// ```
+    pub fn new(
+        launchpads: Vec<Launchpad>,
+        columns: usize,
+    ) -> Result<MultiLaunchpad, LaunchpadError> {
+        if launchpads.is_empty() || !launchpads.len().is_multiple_of(columns) {
+            return Err(LaunchpadError::BadLayout(format!(
+                "{} launchpads don't fill rows of {}",
+                launchpads.len(),
+                columns
+            )));
+        }
+        let rows = launchpads.len() / columns;
+        let disconnected = vec![false; launchpads.len()];
+        let tiles = launchpads
+            .into_iter()
+            .enumerate()
+            .map(|(i, lp)| (lp, i % columns, i / columns))
+            .collect();
+        Ok(MultiLaunchpad {
+            tiles,
+            columns,
+            rows,
+            disconnected,
+        })
+    }
+    /// Opens the launchpads on the given ports and tiles them as laid out.
+    /// Rows are listed top to bottom, the way they sit on the desk,
+    /// and each port is an index or a name from list_ports.
+    /// Fails with NoDevice if a port isn't a launchpad,
+    /// and BadLayout if the rows aren't all the same length or a port is used twice.
+    pub fn open_layout(layout: &[Vec<String>]) -> Result<MultiLaunchpad, LaunchpadError> {
+        let columns = layout.first().map_or(0, Vec::len);
+        if layout.iter().any(|row| row.len() != columns) {
+            return Err(LaunchpadError::BadLayout(
+                "every row needs the same number of launchpads".into(),
+            ));
+        }
+        let ports = Launchpad::list_ports()?;
+        let mut picked: Vec<&PortInfo> = Vec::new();
+        for wanted in layout.iter().rev().flatten() {
+            let port = ports
+                .iter()
+                .find(|port| match wanted.parse() {
+                    Ok(index) => port.index == index,
+                    Err(_) => port.name == *wanted,
+                })
+                .ok_or(LaunchpadError::NoDevice)?;
+            if picked.iter().any(|p| p.index == port.index) {
+                return Err(LaunchpadError::BadLayout(format!(
+                    "{} is used more than once",
+                    wanted
+                )));
+            }
+            picked.push(port);
+        }
+        let launchpads = picked
+            .iter()
+            .enumerate()
+            .map(|(i, port)| Launchpad::open_port_with(port, i == 0))
+            .collect::<Result<_, _>>()?;
+        MultiLaunchpad::new(launchpads, columns)
+    }
+    /// Returns the launchpads, in the order they were tiled
+    pub fn launchpads(&mut self) -> impl Iterator<Item = &mut Launchpad> {
+        self.tiles.iter_mut().map(|(lp, _, _)| lp)
+    }
+    /// Gives every launchpad the same bindings.
+    /// Pads are bound by their place on each launchpad, not in the whole grid.
+    pub fn set_bindings(&mut self, bindings: Bindings) {
+        self.launchpads()
+            .for_each(|lp| lp.set_bindings(bindings.clone()));
+    }
+    /// Records every launchpad's traffic to the same recording, or stops if given None.
+    /// Each launchpad's messages are marked with its place in the order they were tiled.
+    pub fn set_recorder(&mut self, recorder: Option<Recorder>) {
+        for (tile, lp) in self.launchpads().enumerate() {
+            lp.set_recorder(recorder.as_ref().map(|recorder| recorder.for_tile(tile)));
+        }
+    }
+    /// Resends every launchpad's last frame in full
+    pub fn refresh(&mut self) -> Result<(), LaunchpadError> {
+        self.launchpads().try_for_each(Launchpad::refresh)
+    }
+    /// Stops any scrolling text on every launchpad
+    pub fn stop_scroll(&mut self) -> Result<(), LaunchpadError> {
+        self.launchpads().try_for_each(Launchpad::stop_scroll)
+    }
+    /// Finds the launchpad showing the given pad, and the pad's place on it
+    fn tile(&mut self, pad: Pad) -> Option<(&mut Launchpad, Pad)> {
+        let (column, row) = (pad.x as usize / 8, pad.y as usize / 8);
+        let local = Pad {
+            x: pad.x % 8,
+            y: pad.y % 8,
+        };
+        self.tiles
+            .iter_mut()
+            .find(|(_, c, r)| (*c, *r) == (column, row))
+            .map(|(lp, _, _)| (lp, local))
+    }
+    /// Cuts out the 8x8 piece of the matrix shown by the tile at (column, row).
+    /// Anything past the edge of the matrix is filled with `empty`.
+    fn cut<T: Clone>(matrix: &Array2D<T>, column: usize, row: usize, empty: T) -> Array2D<T> {
+        let mut tile = Array2D::filled_with(empty, 8, 8);
+        for y in 0..8 {
+            for x in 0..8 {
+                if let Some(value) = matrix.get(row * 8 + y, column * 8 + x) {
+                    tile.set(y, x, value.clone()).ok();
+                }
+            }
+        }
+        tile
+    }
+}
+// Input defs
+impl MultiLaunchpad {
+    /// Gets the next ControlEvent from any of the launchpads, skipping releases.
+    /// Disconnected is only sent for the first one to go,
+    /// and Reconnected once they're all back.
+    pub fn poll_input(&mut self) -> Option<ControlEvent> {
+        loop {
+            match self.poll_event()? {
+                InputEvent {
+                    event,
+                    pressed: true,
+                    ..
+                } => return Some(event),
+                _ => continue,
+            }
+        }
+    }
+    /// Gets the next InputEvent from any of the launchpads, presses and releases both
+    pub fn poll_event(&mut self) -> Option<InputEvent> {
+        for i in 0..self.tiles.len() {
+            let input = match self.tiles[i].0.poll_event() {
+                Some(input) => input,
+                None => continue,
+            };
+            let was_down = self.disconnected.contains(&true);
+            match input.event {
+                ControlEvent::Disconnected => self.disconnected[i] = true,
+                ControlEvent::Reconnected => self.disconnected[i] = false,
+                _ => return Some(input),
+            }
+            let is_down = self.disconnected.contains(&true);
+            // Going down or coming back up is what was just polled
+            if was_down != is_down {
+                return Some(input);
+            }
+        }
+        None
+    }
+}
+impl Controller for MultiLaunchpad {
+    fn poll_event(&mut self) -> Option<InputEvent> {
+        MultiLaunchpad::poll_event(self)
+    }
+    /// Pulses each launchpad's part of the matrix
+    fn pulse_matrix(&mut self, matrix: Array2D<u8>) -> Result<(), LaunchpadError> {
+        for (lp, column, row) in self.tiles.iter_mut() {
+            lp.pulse_matrix(MultiLaunchpad::cut(&matrix, *column, *row, 0))?;
+        }
+        Ok(())
+    }
+    fn stop_scroll(&mut self) -> Result<(), LaunchpadError> {
+        MultiLaunchpad::stop_scroll(self)
+    }
+    fn set_bindings(&mut self, bindings: Bindings) {
+        MultiLaunchpad::set_bindings(self, bindings)
+    }
+    fn set_recorder(&mut self, recorder: Option<Recorder>) {
+        MultiLaunchpad::set_recorder(self, recorder)
+    }
+}
+impl GridDisplay for MultiLaunchpad {
+    type Error = LaunchpadError;
+
+    fn size(&self) -> (usize, usize) {
+        (self.columns * 8, self.rows * 8)
+    }
+    fn clear(&mut self) -> Result<(), LaunchpadError> {
+        self.launchpads().try_for_each(Launchpad::clear)
+    }
+    /// Does nothing if the pad isn't on any of the launchpads
+    fn send_pad(&mut self, pad: Pad, velocity: u8) -> Result<(), LaunchpadError> {
+        match self.tile(pad) {
+            Some((lp, pad)) => lp.send_pad(pad, velocity),
+            None => Ok(()),
+        }
+    }
+    /// Fails with NoSuchButton if the button is past the last tile
+    fn send_button(&mut self, button: Button, velocity: u8) -> Result<(), LaunchpadError> {
+        let requested = button;
+        let (column, row, button) = match button {
+            Button::Grid(pad) => return self.send_pad(pad, velocity),
+            Button::Top(n) => (
+                n as usize / 8,
+                self.rows.saturating_sub(1),
+                Button::Top(n % 8),
+            ),
+            Button::Scene(n) => (
+                self.columns.saturating_sub(1),
+                n as usize / 8,
+                Button::Scene(n % 8),
+            ),
+        };
+        match self
+            .tiles
+            .iter_mut()
+            .find(|(_, c, r)| (*c, *r) == (column, row))
+        {
+            Some((lp, _, _)) => lp.send_button(button, velocity),
+            None => Err(LaunchpadError::NoSuchButton(requested)),
+        }
+    }
+    /// Splits the matrix between the launchpads.
+    /// Pads outside the matrix are turned off, and anything outside the launchpads is dropped.
+    fn send_matrix(&mut self, matrix: Array2D<u8>) -> Result<(), LaunchpadError> {
+        for (lp, column, row) in self.tiles.iter_mut() {
+            lp.send_matrix(MultiLaunchpad::cut(&matrix, *column, *row, 0))?;
+        }
+        Ok(())
+    }
+    fn send_pad_color(&mut self, pad: Pad, color: Color) -> Result<(), LaunchpadError> {
+        match self.tile(pad) {
+            Some((lp, pad)) => lp.send_pad_color(pad, color),
+            None => Ok(()),
+        }
+    }
+    fn send_color_matrix(&mut self, matrix: Array2D<Color>) -> Result<(), LaunchpadError> {
+        for (lp, column, row) in self.tiles.iter_mut() {
+            lp.send_color_matrix(MultiLaunchpad::cut(&matrix, *column, *row, Color::OFF))?;
+        }
+        Ok(())
+    }
+    /// Scrolls the same text across every launchpad
+    fn scroll_text(
+        &mut self,
+        text: &str,
+        velocity: u8,
+        speed: u8,
+        looping: bool,
+    ) -> Result<(), LaunchpadError> {
+        self.launchpads()
+            .try_for_each(|lp| lp.scroll_text(text, velocity, speed, looping))
+    }
+    fn is_scrolling(&self) -> bool {
+        self.tiles.iter().any(|(lp, _, _)| lp.is_scrolling())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MultiLaunchpad;
+    use crate::{
+        mock::{MockOutput, Update},
+        Button, Color, Controller, GridDisplay, Launchpad, LaunchpadError, Pad,
+    };
+    use array2d::Array2D;
+
+    /// Returns a multi launchpad of mocks, and the mocks
+    fn tiled(count: usize, columns: usize) -> (MultiLaunchpad, Vec<MockOutput>) {
+        let outs: Vec<MockOutput> = (0..count).map(|_| MockOutput::new()).collect();
+        let tiles = outs
+            .iter()
+            .map(|out| Launchpad::with_output(out.clone()))
+            .collect();
+        (MultiLaunchpad::new(tiles, columns).unwrap(), outs)
+    }
+
+    #[test]
+    fn stacked() {
+        let (mut multi, outs) = tiled(2, 1);
+        assert_eq!(multi.size(), (8, 16));
+        let mut matrix = Array2D::filled_with(0, 16, 8);
+        matrix.set(0, 0, 5).unwrap();
+        matrix.set(15, 7, 9).unwrap();
+        multi.send_matrix(matrix).unwrap();
+        let mut bottom = Array2D::filled_with(0, 8, 8);
+        bottom.set(0, 0, 5).unwrap();
+        let mut top = Array2D::filled_with(0, 8, 8);
+        top.set(7, 7, 9).unwrap();
+        assert_eq!(outs[0].state(), bottom);
+        assert_eq!(outs[1].state(), top);
+        multi.send_pad(Pad { x: 3, y: 12 }, 13).unwrap();
+        assert_eq!(outs[1].state()[(4, 3)], 13);
+    }
+    #[test]
+    fn square() {
+        let (mut multi, outs) = tiled(4, 2);
+        let vec: Vec<u8> = (0..=255).collect();
+        let matrix = Array2D::from_row_major(&vec, 16, 16);
+        multi.send_matrix(matrix.clone()).unwrap();
+        // Top right tile
+        assert_eq!(outs[3].state()[(0, 0)], matrix[(8, 8)]);
+        assert_eq!(outs[3].state()[(7, 7)], matrix[(15, 15)]);
+        multi.send_button(Button::Top(9), 5).unwrap();
+        multi.send_button(Button::Scene(3), 9).unwrap();
+        assert_eq!(outs[3].button_color(Button::Top(1)), Color::Palette(5));
+        assert_eq!(outs[1].button_color(Button::Scene(3)), Color::Palette(9));
+        // Two tiles wide and two high is 16 of each
+        for button in [Button::Top(16), Button::Scene(16), Button::Scene(255)].iter() {
+            assert_eq!(
+                multi.send_button(*button, 5),
+                Err(LaunchpadError::NoSuchButton(*button))
+            );
+        }
+    }
+    #[test]
+    fn pulse() {
+        let (mut multi, outs) = tiled(2, 1);
+        let mut matrix = Array2D::filled_with(0, 16, 8);
+        matrix.set(12, 3, 5).unwrap();
+        Controller::pulse_matrix(&mut multi, matrix).unwrap();
+        // Only the top one has anything to pulse
+        assert!(outs[0].updates().is_empty());
+        assert_eq!(
+            outs[1].updates(),
+            vec![Update::Pulse(vec![(Button::Grid(Pad { x: 3, y: 4 }), 5)])]
+        );
+    }
+    #[test]
+    fn small_frame() {
+        let (mut multi, outs) = tiled(2, 2);
+        multi.send_matrix(Array2D::filled_with(5, 8, 8)).unwrap();
+        assert_eq!(outs[0].state(), Array2D::filled_with(5, 8, 8));
+        assert_eq!(outs[1].state(), Array2D::filled_with(0, 8, 8));
+    }
+}
@@ -0,0 +1,217 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! The MK2's colour palette, the 128 colours a velocity (or palette index) can pick.
+//! The other models use the same palette.
+
+/// What each palette index looks like, as 8-bit (red, green, blue).
+/// These are the commonly published values, the LEDs themselves vary a bit.
+pub const PALETTE: [(u8, u8, u8); 128] = [
+    (0x00, 0x00, 0x00),
+    (0x1E, 0x1E, 0x1E),
+    (0x7F, 0x7F, 0x7F),
+    (0xFF, 0xFF, 0xFF),
+    (0xFF, 0x4C, 0x4C),
+    (0xFF, 0x00, 0x00),
+    (0x59, 0x00, 0x00),
+    (0x19, 0x00, 0x00),
+    (0xFF, 0xBD, 0x6C),
+    (0xFF, 0x54, 0x00),
+    (0x59, 0x1D, 0x00),
+    (0x27, 0x1B, 0x00),
+    (0xFF, 0xFF, 0x4C),
+    (0xFF, 0xFF, 0x00),
+    (0x59, 0x59, 0x00),
+    (0x19, 0x19, 0x00),
+    (0x88, 0xFF, 0x4C),
+    (0x54, 0xFF, 0x00),
+    (0x1D, 0x59, 0x00),
+    (0x14, 0x2B, 0x00),
+    (0x4C, 0xFF, 0x4C),
+    (0x00, 0xFF, 0x00),
+    (0x00, 0x59, 0x00),
+    (0x00, 0x19, 0x00),
+    (0x4C, 0xFF, 0x5E),
+    (0x00, 0xFF, 0x19),
+    (0x00, 0x59, 0x0D),
+    (0x00, 0x19, 0x02),
+    (0x4C, 0xFF, 0x88),
+    (0x00, 0xFF, 0x55),
+    (0x00, 0x59, 0x1D),
+    (0x00, 0x1F, 0x12),
+    (0x4C, 0xFF, 0xB7),
+    (0x00, 0xFF, 0x99),
+    (0x00, 0x59, 0x35),
+    (0x00, 0x19, 0x12),
+    (0x4C, 0xC3, 0xFF),
+    (0x00, 0xA9, 0xFF),
+    (0x00, 0x41, 0x52),
+    (0x00, 0x10, 0x19),
+    (0x4C, 0x88, 0xFF),
+    (0x00, 0x55, 0xFF),
+    (0x00, 0x1D, 0x59),
+    (0x00, 0x08, 0x19),
+    (0x4C, 0x4C, 0xFF),
+    (0x00, 0x00, 0xFF),
+    (0x00, 0x00, 0x59),
+    (0x00, 0x00, 0x19),
+    (0x87, 0x4C, 0xFF),
+    (0x54, 0x00, 0xFF),
+    (0x19, 0x00, 0x64),
+    (0x0F, 0x00, 0x30),
+    (0xFF, 0x4C, 0xFF),
+    (0xFF, 0x00, 0xFF),
+    (0x59, 0x00, 0x59),
+    (0x19, 0x00, 0x19),
+    (0xFF, 0x4C, 0x87),
+    (0xFF, 0x00, 0x54),
+    (0x59, 0x00, 0x1D),
+    (0x22, 0x00, 0x13),
+    (0xFF, 0x15, 0x00),
+    (0x99, 0x35, 0x00),
+    (0x79, 0x51, 0x00),
+    (0x43, 0x64, 0x00),
+    (0x03, 0x39, 0x00),
+    (0x00, 0x57, 0x35),
+    (0x00, 0x54, 0x7F),
+    (0x00, 0x00, 0xFF),
+    (0x00, 0x45, 0x4F),
+    (0x25, 0x00, 0xCC),
+    (0x7F, 0x7F, 0x7F),
+    (0x20, 0x20, 0x20),
+    (0xFF, 0x00, 0x00),
+    (0xBD, 0xFF, 0x2D),
+    (0xAF, 0xED, 0x06),
+    (0x64, 0xFF, 0x09),
+    (0x10, 0x8B, 0x00),
+    (0x00, 0xFF, 0x87),
+    (0x00, 0xA9, 0xFF),
+    (0x00, 0x2A, 0xFF),
+    (0x3F, 0x00, 0xFF),
+    (0x7A, 0x00, 0xFF),
+    (0xB2, 0x1A, 0x7D),
+    (0x40, 0x21, 0x00),
+    (0xFF, 0x4A, 0x00),
+    (0x88, 0xE1, 0x06),
+    (0x72, 0xFF, 0x15),
+    (0x00, 0xFF, 0x00),
+    (0x3B, 0xFF, 0x26),
+    (0x59, 0xFF, 0x71),
+    (0x38, 0xFF, 0xCC),
+    (0x5B, 0x8A, 0xFF),
+    (0x31, 0x51, 0xC6),
+    (0x87, 0x7F, 0xE9),
+    (0xD3, 0x1D, 0xFF),
+    (0xFF, 0x00, 0x5D),
+    (0xFF, 0x7F, 0x00),
+    (0xB9, 0xB0, 0x00),
+    (0x90, 0xFF, 0x00),
+    (0x83, 0x5D, 0x07),
+    (0x39, 0x2B, 0x00),
+    (0x14, 0x4C, 0x10),
+    (0x0D, 0x50, 0x38),
+    (0x15, 0x15, 0x2A),
+    (0x16, 0x20, 0x5A),
+    (0x69, 0x3C, 0x1C),
+    (0xA8, 0x00, 0x0A),
+    (0xDE, 0x51, 0x3D),
+    (0xD8, 0x6A, 0x1C),
+    (0xFF, 0xE1, 0x26),
+    (0x9E, 0xE1, 0x2F),
+    (0x67, 0xB5, 0x0F),
+    (0x1E, 0x1E, 0x30),
+    (0xDC, 0xFF, 0x6B),
+    (0x80, 0xFF, 0xBD),
+    (0x9A, 0x99, 0xFF),
+    (0x8E, 0x66, 0xFF),
+    (0x40, 0x40, 0x40),
+    (0x75, 0x75, 0x75),
+    (0xE0, 0xFF, 0xFF),
+    (0xA0, 0x00, 0x00),
+    (0x35, 0x00, 0x00),
+    (0x1A, 0xD0, 0x00),
+    (0x07, 0x42, 0x00),
+    (0xB9, 0xB0, 0x00),
+    (0x3F, 0x31, 0x00),
+    (0xB3, 0x5F, 0x00),
+    (0x4B, 0x15, 0x02),
+];
+
+// Named colours, each the brightest of its group.
+// Most hues also have a paler version one below and dimmer ones one and two above,
+// i.e. RED - 1 is pale red and RED + 1 is dark red.
+pub const OFF: u8 = 0;
+pub const DARK_GREY: u8 = 1;
+pub const GREY: u8 = 2;
+pub const WHITE: u8 = 3;
+pub const RED: u8 = 5;
+pub const ORANGE: u8 = 9;
+pub const YELLOW: u8 = 13;
+pub const LIME: u8 = 17;
+pub const GREEN: u8 = 21;
+pub const SPRING_GREEN: u8 = 29;
+pub const TURQUOISE: u8 = 33;
+pub const SKY_BLUE: u8 = 37;
+pub const OCEAN_BLUE: u8 = 41;
+pub const BLUE: u8 = 45;
+pub const VIOLET: u8 = 49;
+pub const MAGENTA: u8 = 53;
+pub const PINK: u8 = 57;
+
+/// Returns what a palette index looks like, as 8-bit (red, green, blue).
+/// Only the low 7 bits of the index are used, same as on the device.
+/// ```
+/// # use lp_tetris::palette;
+/// assert_eq!(palette::rgb(palette::RED), (255, 0, 0));
+/// assert_eq!(palette::rgb(palette::OFF), (0, 0, 0));
+/// ```
+pub fn rgb(index: u8) -> (u8, u8, u8) {
+    PALETTE[usize::from(index & 0x7F)]
+}
+
+/// Returns the palette index that looks most like the given 8-bit (red, green, blue).
+/// Where the palette has the same colour twice, the lower index is returned.
+/// ```
+/// # use lp_tetris::palette;
+/// assert_eq!(palette::nearest(255, 0, 0), palette::RED);
+/// assert_eq!(palette::nearest(250, 250, 10), palette::YELLOW);
+/// assert_eq!(palette::nearest(0, 0, 0), palette::OFF);
+/// ```
+pub fn nearest(r: u8, g: u8, b: u8) -> u8 {
+    let distance = |(pr, pg, pb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (i32::from(a) - i32::from(b)).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+    (0..128u8)
+        .min_by_key(|index| distance(rgb(*index)))
+        .unwrap_or(OFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nearest, rgb};
+
+    #[test]
+    fn round_trip() {
+        for index in 0..128 {
+            assert_eq!(
+                rgb(nearest(rgb(index).0, rgb(index).1, rgb(index).2)),
+                rgb(index)
+            );
+        }
+    }
+}
@@ -0,0 +1,341 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Recording MIDI traffic to a file, and playing recordings back.
+//! Recordings are text, one message per line: microseconds since recording started,
+//! which tile it's for when playing with `--multi` (left out for the first),
+//! `out` or `in`, then the message's bytes in hex.
+//! ```text
+//! 0 out F0 00 20 29 02 18 22 00 F7
+//! 4120 out F0 00 20 29 02 18 0A 0B 05 F7
+//! 4290 1 out F0 00 20 29 02 18 0A 0B 05 F7
+//! 250311 in 90 0B 7F
+//! ```
+
+use crate::{LaunchpadError, MidiSink};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Which way a message went
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent to the device
+    Out,
+    /// Received from the device
+    In,
+}
+
+/// One message in a recording
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// How long after the recording started it was sent or received
+    pub time: Duration,
+    /// Which launchpad it went to or came from, as numbered by MultiLaunchpad.
+    /// Always 0 with a single launchpad.
+    pub tile: usize,
+    pub direction: Direction,
+    pub msg: Vec<u8>,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Out => "out",
+            Direction::In => "in",
+        };
+        write!(f, "{}", self.time.as_micros())?;
+        if self.tile != 0 {
+            write!(f, " {}", self.tile)?;
+        }
+        write!(f, " {}", direction)?;
+        for byte in &self.msg {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Entry {
+    type Err = String;
+
+    /// Parses a line of a recording
+    /// ```
+    /// # use lp_tetris::record::{Direction, Entry};
+    /// # use std::time::Duration;
+    /// let entry: Entry = "1500 in 90 0B 7F".parse().unwrap();
+    /// assert_eq!(entry.time, Duration::from_micros(1500));
+    /// assert_eq!(entry.direction, Direction::In);
+    /// assert_eq!(entry.msg, vec![0x90, 0x0B, 0x7F]);
+    /// assert_eq!(entry.to_string(), "1500 in 90 0B 7F");
+    /// let entry: Entry = "1500 2 out F7".parse().unwrap();
+    /// assert_eq!(entry.tile, 2);
+    /// assert_eq!(entry.to_string(), "1500 2 out F7");
+    /// ```
+    fn from_str(line: &str) -> Result<Entry, String> {
+        let mut words = line.split_whitespace();
+        let time = words
+            .next()
+            .and_then(|time| time.parse().ok())
+            .map(Duration::from_micros)
+            .ok_or("expected a time in microseconds")?;
+        let mut word = words.next();
+        let tile = match word.map(str::parse) {
+            Some(Ok(tile)) => {
+                word = words.next();
+                tile
+            }
+            _ => 0,
+        };
+        let direction = match word {
+            Some("out") => Direction::Out,
+            Some("in") => Direction::In,
+            _ => return Err("expected `out` or `in`".to_string()),
+        };
+        let msg = words
+            .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| format!("bad byte `{}`", byte)))
+            .collect::<Result<Vec<u8>, String>>()?;
+        if msg.is_empty() {
+            return Err("empty message".to_string());
+        }
+        Ok(Entry {
+            time,
+            tile,
+            direction,
+            msg,
+        })
+    }
+}
+
+/// Writes MIDI messages to a recording as they happen.
+/// Clones write to the same recording, see for_tile for telling launchpads apart in it.
+/// Each line is written as soon as it's recorded, so nothing is lost if the game crashes.
+#[derive(Clone)]
+pub struct Recorder {
+    inner: Arc<Mutex<Inner>>,
+    /// What Entry.tile is set to
+    tile: usize,
+}
+
+struct Inner {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+impl Recorder {
+    /// Starts a recording in a new file, replacing it if it exists
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Recorder> {
+        Ok(Recorder::new(LineWriter::new(File::create(path)?)))
+    }
+    /// Starts a recording written to `out`. Times count from now.
+    pub fn new<W: Write + Send + 'static>(out: W) -> Recorder {
+        Recorder {
+            inner: Arc::new(Mutex::new(Inner {
+                out: Box::new(out),
+                start: Instant::now(),
+            })),
+            tile: 0,
+        }
+    }
+    /// Returns a recorder writing to the same recording,
+    /// with everything it records marked as going to or from the given tile
+    pub fn for_tile(&self, tile: usize) -> Recorder {
+        Recorder {
+            inner: self.inner.clone(),
+            tile,
+        }
+    }
+    /// Writes a message to the recording, timestamped now
+    pub fn record(&self, direction: Direction, msg: &[u8]) -> io::Result<()> {
+        let mut inner = match self.inner.lock() {
+            Ok(inner) => inner,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let entry = Entry {
+            time: inner.start.elapsed(),
+            tile: self.tile,
+            direction,
+            msg: msg.to_vec(),
+        };
+        writeln!(inner.out, "{}", entry)
+    }
+}
+
+/// Reads a recording from a file
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Entry>> {
+    read(BufReader::new(File::open(path)?))
+}
+
+/// Reads a recording. Blank lines and lines starting with `#` are skipped,
+/// so notes can be added to a recording before sending it in.
+pub fn read<R: BufRead>(reader: R) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let entry = line.parse().map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Sends every outgoing message in a recording to the sink for its tile,
+/// skipping the incoming ones and any for tiles past the end of `sinks`.
+/// If `realtime` is set the messages are spaced out the way they were recorded,
+/// otherwise they're sent as fast as possible.
+pub fn replay(
+    entries: &[Entry],
+    sinks: &mut [&mut dyn MidiSink],
+    realtime: bool,
+) -> Result<(), LaunchpadError> {
+    let start = Instant::now();
+    for entry in entries {
+        if entry.direction != Direction::Out {
+            continue;
+        }
+        let sink = match sinks.get_mut(entry.tile) {
+            Some(sink) => sink,
+            None => continue,
+        };
+        if realtime {
+            if let Some(wait) = entry.time.checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        sink.send(&entry.msg)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read, replay, Direction, Entry, Recorder};
+    use crate::{mock::MockOutput, GridDisplay, Launchpad, MultiLaunchpad, Pad};
+    use std::{
+        io::{self, Write},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    /// A Write that can still be read after it's been handed to a Recorder
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_launchpad() {
+        let buf = SharedBuf::default();
+        let out = MockOutput::new();
+        let mut lp = Launchpad::with_output(out.clone());
+        lp.set_recorder(Some(Recorder::new(buf.clone())));
+        lp.clear().unwrap();
+        lp.send_pad(Pad { x: 0, y: 0 }, 5).unwrap();
+        lp.set_recorder(None);
+        lp.clear().unwrap();
+
+        let entries = read(&buf.0.lock().unwrap()[..]).unwrap();
+        let recorded: Vec<Vec<u8>> = entries.iter().map(|entry| entry.msg.clone()).collect();
+        assert_eq!(recorded, out.messages()[..2].to_vec());
+        assert!(entries
+            .iter()
+            .all(|entry| entry.direction == Direction::Out));
+        assert!(entries[0].time <= entries[1].time);
+    }
+    #[test]
+    fn replay_to_mock() {
+        let text = "# cleared, then a button press\n\
+                    0 out F0 00 20 29 02 18 0E 00 F7\n\
+                    \n\
+                    10 in 90 0B 7F\n\
+                    20 out F0 00 20 29 02 18 0A 0B 05 F7\n";
+        let entries = read(text.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 3);
+        let mut out = MockOutput::new();
+        replay(&entries, &mut [&mut out], true).unwrap();
+        assert_eq!(out.messages().len(), 2);
+        assert_eq!(out.state()[(0, 0)], 5);
+    }
+    #[test]
+    fn tiles() {
+        let buf = SharedBuf::default();
+        let outs = [MockOutput::new(), MockOutput::new()];
+        let tiles = outs
+            .iter()
+            .map(|out| Launchpad::with_output(out.clone()))
+            .collect();
+        let mut multi = MultiLaunchpad::new(tiles, 1).unwrap();
+        multi.set_recorder(Some(Recorder::new(buf.clone())));
+        multi.send_pad(Pad { x: 0, y: 0 }, 5).unwrap();
+        multi.send_pad(Pad { x: 1, y: 9 }, 9).unwrap();
+
+        let entries = read(&buf.0.lock().unwrap()[..]).unwrap();
+        assert_eq!(
+            entries.iter().map(|entry| entry.tile).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        let (mut bottom, mut top) = (MockOutput::new(), MockOutput::new());
+        replay(&entries, &mut [&mut bottom, &mut top], false).unwrap();
+        assert_eq!(bottom.messages(), outs[0].messages());
+        assert_eq!(top.messages(), outs[1].messages());
+        assert_eq!(top.state()[(1, 1)], 9);
+    }
+    #[test]
+    fn bad_lines() {
+        let error = |text: &str| read(text.as_bytes()).unwrap_err().to_string();
+        assert_eq!(
+            error("0 out F7\n5 sideways F7"),
+            "line 2: expected `out` or `in`"
+        );
+        assert_eq!(error("0 out ZZ"), "line 1: bad byte `ZZ`");
+        assert_eq!(
+            error("soon out F7"),
+            "line 1: expected a time in microseconds"
+        );
+        assert_eq!(error("0 in"), "line 1: empty message");
+        assert!("-1 in F7".parse::<Entry>().is_err());
+        assert_eq!(
+            "3 out F7".parse::<Entry>().unwrap().time,
+            Duration::from_micros(3)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Delayed Auto Shift and Auto Repeat Rate, for holding down movement keys.
+
+use crate::{ControlEvent, InputEvent};
+use std::time::{Duration, Instant};
+
+/// Repeats held movement the way most tetris games do.
+/// Left and right repeat after `delay` (DAS), then every `interval` (ARR).
+/// Only the last of them pressed repeats, and releasing it goes back to the other if it's still held.
+/// Soft drop (MoveDown) repeats every `interval` straight away.
+///
+/// The first move happens on the press itself, this only produces the repeats after it.
+/// ```
+/// # use lp_tetris::{AutoRepeat, ControlEvent, InputEvent};
+/// # use std::time::{Duration, Instant};
+/// let start = Instant::now();
+/// let ms = |n| start + Duration::from_millis(n);
+/// let mut repeat = AutoRepeat::new(Duration::from_millis(150), Duration::from_millis(50));
+/// repeat.input(InputEvent::press(ControlEvent::MoveLeft).at(start));
+/// assert_eq!(repeat.poll(ms(100)), None);
+/// assert_eq!(repeat.poll(ms(150)), Some(ControlEvent::MoveLeft));
+/// assert_eq!(repeat.poll(ms(150)), None);
+/// assert_eq!(repeat.poll(ms(200)), Some(ControlEvent::MoveLeft));
+/// repeat.input(InputEvent::release(ControlEvent::MoveLeft).at(ms(210)));
+/// assert_eq!(repeat.poll(ms(300)), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AutoRepeat {
+    delay: Duration,
+    interval: Duration,
+    /// Held left/right moves, most recently pressed last, and when each next repeats
+    shift: Vec<(ControlEvent, Instant)>,
+    /// When soft drop next repeats, if it's held
+    soft_drop: Option<Instant>,
+}
+
+impl Default for AutoRepeat {
+    /// 167ms delay and 33ms interval, 10 and 2 frames at 60fps
+    fn default() -> AutoRepeat {
+        AutoRepeat::new(Duration::from_millis(167), Duration::from_millis(33))
+    }
+}
+
+impl AutoRepeat {
+    pub fn new(delay: Duration, interval: Duration) -> AutoRepeat {
+        AutoRepeat {
+            delay,
+            interval,
+            shift: Vec::new(),
+            soft_drop: None,
+        }
+    }
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+    /// Notes a press or release, as of when it happened.
+    /// Anything other than movement is ignored.
+    pub fn input(&mut self, input: InputEvent) {
+        let now = input.time;
+        match input.event {
+            ControlEvent::MoveLeft | ControlEvent::MoveRight => {
+                self.shift.retain(|(event, _)| *event != input.event);
+                if input.pressed {
+                    self.shift.push((input.event, now + self.delay));
+                } else if let Some((_, next)) = self.shift.last_mut() {
+                    // Whatever's still held starts over
+                    *next = now + self.delay;
+                }
+            }
+            ControlEvent::MoveDown if input.pressed => self.soft_drop = Some(now + self.interval),
+            ControlEvent::MoveDown => self.soft_drop = None,
+            _ => (),
+        }
+    }
+    /// Forgets everything held, for when releases might be missed, e.g. while the game is paused.
+    /// Keys are held again on their next press.
+    pub fn clear(&mut self) {
+        self.shift.clear();
+        self.soft_drop = None;
+    }
+    /// Returns a move that's due to repeat, if any. Call until it returns None.
+    /// Each move repeats at most once for any given `now`, so an interval shorter than
+    /// the time between frames just repeats every frame.
+    pub fn poll(&mut self, now: Instant) -> Option<ControlEvent> {
+        let interval = self.interval;
+        let due = |next: &mut Instant| {
+            if now < *next {
+                return false;
+            }
+            *next = (*next + interval).max(now + Duration::from_nanos(1));
+            true
+        };
+        if self.soft_drop.as_mut().is_some_and(due) {
+            return Some(ControlEvent::MoveDown);
+        }
+        // Only the last one pressed moves
+        let (event, next) = self.shift.last_mut()?;
+        if due(next) {
+            Some(*event)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AutoRepeat;
+    use crate::{ControlEvent, InputEvent};
+    use std::time::{Duration, Instant};
+
+    fn repeats(repeat: &mut AutoRepeat, now: Instant) -> Vec<ControlEvent> {
+        std::iter::from_fn(|| repeat.poll(now)).collect()
+    }
+
+    #[test]
+    fn last_pressed_wins() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut repeat = AutoRepeat::new(Duration::from_millis(100), Duration::from_millis(20));
+        repeat.input(InputEvent::press(ControlEvent::MoveLeft).at(start));
+        repeat.input(InputEvent::press(ControlEvent::MoveRight).at(ms(50)));
+        assert_eq!(repeats(&mut repeat, ms(120)), vec![]);
+        assert_eq!(repeats(&mut repeat, ms(150)), vec![ControlEvent::MoveRight]);
+        // Back to left, after its own delay
+        repeat.input(InputEvent::release(ControlEvent::MoveRight).at(ms(160)));
+        assert_eq!(repeats(&mut repeat, ms(200)), vec![]);
+        assert_eq!(repeats(&mut repeat, ms(260)), vec![ControlEvent::MoveLeft]);
+    }
+    #[test]
+    fn soft_drop() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut repeat = AutoRepeat::new(Duration::from_millis(100), Duration::from_millis(20));
+        repeat.input(InputEvent::press(ControlEvent::MoveDown).at(start));
+        repeat.input(InputEvent::press(ControlEvent::MoveLeft).at(start));
+        assert_eq!(repeats(&mut repeat, ms(20)), vec![ControlEvent::MoveDown]);
+        assert_eq!(
+            repeats(&mut repeat, ms(100)),
+            vec![ControlEvent::MoveDown, ControlEvent::MoveLeft]
+        );
+        repeat.clear();
+        assert_eq!(repeats(&mut repeat, ms(500)), vec![]);
+    }
+    #[test]
+    fn ignores_the_rest() {
+        let mut repeat = AutoRepeat::default();
+        let start = Instant::now();
+        repeat.input(InputEvent::press(ControlEvent::RotateLeft).at(start));
+        repeat.input(InputEvent::press(ControlEvent::DropBlock).at(start));
+        assert_eq!(repeats(&mut repeat, start + Duration::from_secs(1)), vec![]);
+    }
+}
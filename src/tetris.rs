@@ -16,14 +16,16 @@
  */
 
 use array2d::Array2D;
-use rand::{
-    distributions::{Distribution, Standard},
-    Rng,
-};
+use lp_tetris::palette;
 use std::cmp;
 use std::convert::TryInto;
 
-#[derive(Debug)]
+mod random;
+mod rotation;
+pub use random::{by_name as randomizer, Randomizer};
+pub use rotation::{by_name as rotation_system, RotationSystem, Srs};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Rotation {
     Zero,
     HalfPi,
@@ -35,9 +37,10 @@ pub enum CollisionResult {
     Unobstructed,
     Collides,
     CollidesHBound,
-    // AboveRoof
+    /// Sticks out above the hidden rows at the top of the board
+    AboveRoof,
 }
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Tetromino {
     S,
     J,
@@ -47,23 +50,21 @@ pub enum Tetromino {
     Z,
     O,
 }
-impl Distribution<Tetromino> for Standard {
-    /// Implements random selection of tetrominos
-    /// This could be replaced, i.e. if there's a Correct(TM) random distribution, implement it here.
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Tetromino {
-        match rng.gen_range(0, 7) {
-            0 => Tetromino::S,
-            1 => Tetromino::J,
-            2 => Tetromino::L,
-            3 => Tetromino::I,
-            4 => Tetromino::T,
-            5 => Tetromino::Z,
-            _ => Tetromino::O,
-        }
-    }
+impl Tetromino {
+    /// Every tetromino
+    pub const ALL: [Tetromino; 7] = [
+        Tetromino::S,
+        Tetromino::J,
+        Tetromino::L,
+        Tetromino::I,
+        Tetromino::T,
+        Tetromino::Z,
+        Tetromino::O,
+    ];
 }
 #[derive(Debug)]
 pub struct Piece {
+    tetromino: Tetromino,
     layout: Array2D<bool>,
     pub color: u8,
     rotation: Rotation,
@@ -75,46 +76,53 @@ impl Piece {
     pub fn new(id: Tetromino) -> Piece {
         match id {
             Tetromino::S => Piece {
-                layout: Array2D::from_rows(&vec![vec![false, true, true], vec![true, true, false]]),
-                color: 5,
+                tetromino: Tetromino::S,
+                layout: Array2D::from_rows(&[vec![false, true, true], vec![true, true, false]]),
+                color: palette::RED,
                 rotation: Rotation::Zero,
             },
             Tetromino::J => Piece {
-                layout: Array2D::from_rows(&vec![
+                tetromino: Tetromino::J,
+                layout: Array2D::from_rows(&[
                     vec![false, true],
                     vec![false, true],
                     vec![true, true],
                 ]),
-                color: 13,
+                color: palette::YELLOW,
                 rotation: Rotation::Zero,
             },
             Tetromino::L => Piece {
-                layout: Array2D::from_rows(&vec![
+                tetromino: Tetromino::L,
+                layout: Array2D::from_rows(&[
                     vec![true, false],
                     vec![true, false],
                     vec![true, true],
                 ]),
-                color: 21,
+                color: palette::GREEN,
                 rotation: Rotation::Zero,
             },
             Tetromino::I => Piece {
-                layout: Array2D::from_rows(&vec![vec![true], vec![true], vec![true], vec![true]]),
-                color: 3,
+                tetromino: Tetromino::I,
+                layout: Array2D::from_rows(&[vec![true], vec![true], vec![true], vec![true]]),
+                color: palette::WHITE,
                 rotation: Rotation::Zero,
             },
             Tetromino::T => Piece {
-                layout: Array2D::from_rows(&vec![vec![true, true, true], vec![false, true, false]]),
-                color: 37,
+                tetromino: Tetromino::T,
+                layout: Array2D::from_rows(&[vec![true, true, true], vec![false, true, false]]),
+                color: palette::SKY_BLUE,
                 rotation: Rotation::Zero,
             },
             Tetromino::Z => Piece {
-                layout: Array2D::from_rows(&vec![vec![true, true, false], vec![false, true, true]]),
-                color: 45,
+                tetromino: Tetromino::Z,
+                layout: Array2D::from_rows(&[vec![true, true, false], vec![false, true, true]]),
+                color: palette::BLUE,
                 rotation: Rotation::Zero,
             },
             Tetromino::O => Piece {
-                layout: Array2D::from_rows(&vec![vec![true, true], vec![true, true]]),
-                color: 53,
+                tetromino: Tetromino::O,
+                layout: Array2D::from_rows(&[vec![true, true], vec![true, true]]),
+                color: palette::MAGENTA,
                 rotation: Rotation::Zero,
             },
         }
@@ -144,9 +152,17 @@ impl Piece {
     }
     /// Returns a right-rotated version of the piece
     pub fn rotated_right(mut self) -> Piece {
-        self.rotate_left();
+        self.rotate_right();
         self
     }
+    /// Returns which tetromino the piece is
+    pub fn tetromino(&self) -> Tetromino {
+        self.tetromino
+    }
+    /// Returns how the piece is rotated
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
     /// Returns an Array2D calculated from the internal layout and rotations
     pub fn render(&self) -> Array2D<bool> {
         match &self.rotation {
@@ -170,60 +186,103 @@ impl Piece {
     }
 }
 
+/// How many hidden rows there are above the visible part of the board for pieces to spawn into,
+/// enough for an I standing on end
+pub const BUFFER_ROWS: usize = 4;
+
 #[derive(Debug)]
 pub struct Board {
+    /// The visible rows, then BUFFER_ROWS hidden ones
     matrix: Array2D<u8>,
 }
 
 impl Board {
-    /// Returns a new, empty board
+    /// Returns a new, empty 8x8 board, the size of one launchpad
+    #[allow(unused)]
     pub fn new() -> Board {
+        Board::with_size(8, 8)
+    }
+    /// Returns a new, empty board of the given size, e.g. 10x20 for a standard well.
+    /// The hidden rows go on top of that.
+    pub fn with_size(width: usize, height: usize) -> Board {
         Board {
-            matrix: Array2D::filled_with(0, 8, 8),
+            matrix: Array2D::filled_with(0, height + BUFFER_ROWS, width),
         }
     }
+    /// Returns how many columns the board has
+    pub fn width(&self) -> usize {
+        self.matrix.num_columns()
+    }
+    /// Returns how many rows of the board can be seen
+    pub fn height(&self) -> usize {
+        self.matrix.num_rows() - BUFFER_ROWS
+    }
+    /// Returns how many rows the board has, hidden ones included
+    pub fn total_height(&self) -> usize {
+        self.matrix.num_rows()
+    }
+    /// Copies the matrix with the piece added, leaving out any of it that's off the board
     fn place_impl(&self, piece: &Piece, x: usize, y: usize) -> Array2D<u8> {
         let mut new_matrix = Array2D::from_rows(&self.matrix.as_rows());
         let render = piece.render();
         for iy in (0..render.num_rows()).rev() {
             for ix in 0..render.num_columns() {
-                match render.get(render.num_rows().saturating_sub(1) - iy, ix) {
-                    Some(true) => {
-                        new_matrix.set(y + iy, x + ix, piece.color).ok();
+                if let Some(true) = render.get(render.num_rows().saturating_sub(1) - iy, ix) {
+                    if let Some(cell) = new_matrix.get_mut(y + iy, x + ix) {
+                        *cell = piece.color;
                     }
-                    _ => (),
                 };
             }
         }
         new_matrix
     }
-    /// Places a given piece at the given location
-    pub fn place(&mut self, piece: &Piece, x: usize, y: usize) {
-        self.matrix = self.place_impl(&piece, x, y);
+    /// Places a given piece at the given location.
+    /// If any of it would be off the board, the board is left alone
+    /// and the error says which way, CollidesHBound or AboveRoof.
+    pub fn place(&mut self, piece: &Piece, x: usize, y: usize) -> Result<(), CollisionResult> {
+        match self.collides(piece, x, y) {
+            bound @ CollisionResult::CollidesHBound | bound @ CollisionResult::AboveRoof => {
+                Err(bound)
+            }
+            _ => {
+                self.matrix = self.place_impl(piece, x, y);
+                Ok(())
+            }
+        }
     }
-    /// Clones the matrix, adds the given piece to the clone, and returns the clone.
+    /// Clones the matrix, adds the given piece to the clone, and returns the visible part of the clone.
+    /// Whatever of the piece is off the board isn't shown.
     pub fn shadow(&self, piece: &Piece, x: usize, y: usize) -> Array2D<u8> {
-        self.place_impl(&piece, x, y)
+        let mut rows = self.place_impl(piece, x, y).as_rows();
+        rows.truncate(self.height());
+        Array2D::from_rows(&rows)
     }
-    /// Clears all filled rows
-    pub fn clear_rows(&mut self) {
-        for iy in 0..8 {
-            if self.row_filled(iy) == 8 {
+    /// Clears all filled rows, returning how many there were
+    pub fn clear_rows(&mut self) -> u8 {
+        let mut cleared = 0;
+        // Top down, so removing a row only moves rows that have already been checked
+        for iy in (0..self.total_height()).rev() {
+            if usize::from(self.row_filled(iy)) == self.width() {
                 let mut rows = self.matrix.as_rows();
                 rows.remove(iy);
-                let mut new_rows = vec![vec![0, 0, 0, 0, 0, 0, 0, 0]];
-                rows.append(&mut new_rows);
+                rows.push(vec![0; self.width()]);
                 self.matrix = Array2D::from_rows(&rows);
+                cleared += 1;
             }
         }
+        cleared
     }
     /// Returns a count of how many cells in a row are filled
     pub fn row_filled(&self, y: usize) -> u8 {
+        debug_assert!(
+            self.width() <= 255,
+            "boards wider than 255 can't be counted in a u8"
+        );
         self.matrix.row_iter(y).map(|v| cmp::min(1, *v)).sum()
     }
-    /// Returns the height of a column
+    /// Returns the height of a column, which can be into the hidden rows
     pub fn column_height(&self, x: usize) -> u8 {
-        for iy in (0..8).rev() {
+        for iy in (0..self.total_height()).rev() {
             match self.matrix.get(iy, x) {
                 Some(0) => (),
                 None => (),
@@ -232,37 +291,35 @@ impl Board {
         }
         0
     }
-    /// Returns whether the game is over
-    pub fn finished(&self) -> bool {
-        for ix in 0..8 {
-            if self.column_height(ix) == 8 {
-                return true;
-            }
-        }
-        false
+    /// Returns whether locking a piece here ends the game, because none of it would be visible
+    pub fn locked_out(&self, piece: &Piece, y: usize) -> bool {
+        let render = piece.render();
+        // Rows render top to bottom
+        (0..render.num_rows()).all(|iy| {
+            y + iy >= self.height()
+                || !render
+                    .row_iter(render.num_rows() - iy - 1)
+                    .any(|cell| *cell)
+        })
+    }
+    /// Returns whether a piece spawning here ends the game, because the stack is in the way
+    pub fn blocked_out(&self, piece: &Piece, x: usize, y: usize) -> bool {
+        self.collides(piece, x, y) != CollisionResult::Unobstructed
     }
     pub fn collides(&self, piece: &Piece, x: usize, y: usize) -> CollisionResult {
         let render = piece.render();
-        if x + render.num_columns() > 8 {
+        if x + render.num_columns() > self.width() {
             CollisionResult::CollidesHBound
-        // } else if y + render.num_rows() >= 7 {
-        //     CollisionResult::AboveRoof
+        } else if y + render.num_rows() > self.total_height() {
+            CollisionResult::AboveRoof
         } else {
             for iy in 0..render.num_rows() {
                 for ix in 0..render.num_columns() {
-                    if y + iy >= 8 || (x + ix) >= 8 {
-                        continue;
-                    } else if *render
+                    if *render
                         .get(render.num_rows().saturating_sub(iy + 1), ix)
                         .unwrap()
                         && *self.matrix.get(y + iy, x + ix).unwrap() > 0
                     {
-                        println!("Collision at ({}, {}), ({}, {})", y + iy, x + ix, iy, ix);
-                        println!(
-                            "Block Value: {:?}. Matrix Value: {}.",
-                            render.as_rows(),
-                            *self.matrix.get(y + iy, x + ix).unwrap()
-                        );
                         return CollisionResult::Collides;
                     }
                 }
@@ -270,33 +327,25 @@ impl Board {
             CollisionResult::Unobstructed
         }
     }
-    /// Given a rotated piece and its position, attempts a rotation
-    /// BOOKMARK: this is where to go to implement SRS or whatever
-    pub fn try_rotation(&self, piece: &Piece, x: usize, y: usize) -> Option<(usize, usize)> {
-        match self.collides(&piece, x as usize, y as usize) {
-            CollisionResult::Unobstructed => Some((x, y)),
-            // CollisionResult::AboveRoof => Some((x, y)),
-            CollisionResult::CollidesHBound => {
-                for i in 0..piece.render().num_columns() {
-                    match self.collides(&piece, x.saturating_sub(i) as usize, y as usize) {
-                        CollisionResult::Unobstructed => return Some((x.saturating_sub(i), y)),
-                        _ => (),
-                    };
+    /// Given a piece that's just been rotated from `from` and its position, attempts the rotation.
+    /// `system` decides where it goes, returning the new position if it fits anywhere.
+    pub fn try_rotation(
+        &self,
+        system: &dyn RotationSystem,
+        piece: &Piece,
+        from: Rotation,
+        x: usize,
+        y: usize,
+    ) -> Option<(usize, usize)> {
+        rotation::moves(system, piece, from)
+            .into_iter()
+            .find_map(|(dx, dy)| {
+                let (x, y) = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+                match self.collides(piece, x, y) {
+                    CollisionResult::Unobstructed => Some((x, y)),
+                    _ => None,
                 }
-                None
-            }
-            CollisionResult::Collides => {
-                match self.collides(&piece, x.saturating_add(1) as usize, y as usize) {
-                    CollisionResult::Unobstructed => return Some((x.saturating_sub(1), y)),
-                    _ => (),
-                };
-                match self.collides(&piece, x.saturating_sub(1) as usize, y as usize) {
-                    CollisionResult::Unobstructed => return Some((x.saturating_sub(1), y)),
-                    _ => (),
-                };
-                None
-            }
-        }
+            })
     }
 }
 
@@ -363,7 +412,7 @@ mod tests {
     fn added_piece() {
         let mut board = super::Board::new();
         let piece = super::Piece::new(super::Tetromino::L);
-        board.place(&piece, 0, 0);
+        board.place(&piece, 0, 0).unwrap();
         println!("{:?}", board);
         assert_eq!(board.column_height(0), 3);
         assert_eq!(board.column_height(1), 1);
@@ -376,9 +425,15 @@ mod tests {
     #[test]
     fn clear_bottom_row() {
         let mut board = super::Board::new();
-        board.place(&super::Piece::new(super::Tetromino::L), 0, 0);
-        board.place(&super::Piece::new(super::Tetromino::J), 6, 0);
-        board.place(&super::Piece::new(super::Tetromino::I).rotated_left(), 2, 0);
+        board
+            .place(&super::Piece::new(super::Tetromino::L), 0, 0)
+            .unwrap();
+        board
+            .place(&super::Piece::new(super::Tetromino::J), 6, 0)
+            .unwrap();
+        board
+            .place(&super::Piece::new(super::Tetromino::I).rotated_left(), 2, 0)
+            .unwrap();
         assert_eq!(board.column_height(0), 3);
         assert_eq!(board.column_height(1), 1);
         assert_eq!(board.column_height(2), 1);
@@ -404,21 +459,27 @@ mod tests {
     #[test]
     fn clear_floating_row() {
         let mut board = super::Board::new();
-        board.place(
-            &super::Piece::new(super::Tetromino::L)
-                .rotated_left()
-                .rotated_left(),
-            6,
-            0,
-        );
-        board.place(
-            &super::Piece::new(super::Tetromino::J)
-                .rotated_left()
-                .rotated_left(),
-            0,
-            0,
-        );
-        board.place(&super::Piece::new(super::Tetromino::I).rotated_left(), 2, 2);
+        board
+            .place(
+                &super::Piece::new(super::Tetromino::L)
+                    .rotated_left()
+                    .rotated_left(),
+                6,
+                0,
+            )
+            .unwrap();
+        board
+            .place(
+                &super::Piece::new(super::Tetromino::J)
+                    .rotated_left()
+                    .rotated_left(),
+                0,
+                0,
+            )
+            .unwrap();
+        board
+            .place(&super::Piece::new(super::Tetromino::I).rotated_left(), 2, 2)
+            .unwrap();
         assert_eq!(board.column_height(0), 3);
         assert_eq!(board.column_height(1), 3);
         assert_eq!(board.column_height(2), 3);
@@ -442,6 +503,20 @@ mod tests {
         assert_eq!(board.row_filled(3), 0);
     }
     #[test]
+    fn clear_stacked_rows() {
+        let mut board = super::Board::new();
+        for x in (0..8).step_by(2) {
+            board
+                .place(&super::Piece::new(super::Tetromino::O), x, 0)
+                .unwrap();
+        }
+        assert_eq!(board.row_filled(0), 8);
+        assert_eq!(board.row_filled(1), 8);
+        assert_eq!(board.clear_rows(), 2);
+        assert_eq!(board.row_filled(0), 0);
+        assert_eq!(board.clear_rows(), 0);
+    }
+    #[test]
     fn collide_hbound() {
         let board = super::Board::new();
         let piece = super::Piece::new(super::Tetromino::I).rotated_left();
@@ -455,6 +530,85 @@ mod tests {
         );
     }
     #[test]
+    fn standard_well() {
+        let mut board = super::Board::with_size(10, 20);
+        assert_eq!((board.width(), board.height()), (10, 20));
+        let piece = super::Piece::new(super::Tetromino::I).rotated_left();
+        assert_eq!(
+            board.collides(&piece, 6, 0),
+            super::CollisionResult::Unobstructed
+        );
+        assert_eq!(
+            board.collides(&piece, 7, 0),
+            super::CollisionResult::CollidesHBound
+        );
+        board.place(&piece, 0, 0).unwrap();
+        board.place(&piece, 4, 0).unwrap();
+        assert_eq!(board.clear_rows(), 0);
+        board
+            .place(&super::Piece::new(super::Tetromino::O), 8, 0)
+            .unwrap();
+        assert_eq!(board.row_filled(1), 2);
+        assert_eq!(board.clear_rows(), 1);
+        assert_eq!(board.row_filled(0), 2);
+        assert_eq!(board.shadow(&piece, 0, 19).num_rows(), 20);
+        let o = super::Piece::new(super::Tetromino::O);
+        for _ in 0..10 {
+            board.place(&o, 0, board.column_height(0).into()).unwrap();
+        }
+        assert_eq!(board.column_height(0), 20);
+        assert!(!board.blocked_out(&o, 0, 20));
+        assert!(board.blocked_out(&o, 0, 19));
+        // Half in view is fine, all of it above isn't
+        assert!(!board.locked_out(&o, 19));
+        assert!(board.locked_out(&o, 20));
+    }
+    #[test]
+    fn above_the_well() {
+        let mut board = super::Board::new();
+        let o = super::Piece::new(super::Tetromino::O);
+        // Half in the hidden rows is still on the board, only the bottom half shows
+        board.place(&o, 0, 7).unwrap();
+        assert_eq!(board.column_height(0), 9);
+        let shadow = board.shadow(&o, 4, 7);
+        assert_eq!(shadow[(7, 0)], o.color);
+        assert_eq!(shadow[(7, 4)], o.color);
+        // Poking out above the hidden rows, or off the side, doesn't place anything
+        assert_eq!(
+            board.place(&o, 2, 11),
+            Err(super::CollisionResult::AboveRoof)
+        );
+        assert_eq!(
+            board.place(&o, 7, 0),
+            Err(super::CollisionResult::CollidesHBound)
+        );
+        assert_eq!(board.column_height(2), 0);
+        assert_eq!(board.column_height(7), 0);
+        // And drawing it there leaves off what doesn't fit
+        let shadow = board.shadow(&o, 7, 0);
+        assert_eq!(shadow[(0, 7)], o.color);
+        assert_eq!(board.shadow(&o, 2, 11).num_rows(), 8);
+    }
+    #[test]
+    fn hidden_rows() {
+        let mut board = super::Board::new();
+        assert_eq!((board.height(), board.total_height()), (8, 12));
+        let o = super::Piece::new(super::Tetromino::O);
+        board.place(&o, 0, 10).unwrap();
+        assert_eq!(board.column_height(0), 12);
+        // Only the visible rows are shown
+        let shadow = board.shadow(&o, 0, 7);
+        assert_eq!(shadow.num_rows(), 8);
+        assert_eq!(shadow[(7, 0)], o.color);
+        assert!(shadow.as_row_major().iter().filter(|c| **c > 0).count() == 2);
+        // Full rows clear up there too
+        for x in (2..8).step_by(2) {
+            board.place(&o, x, 10).unwrap();
+        }
+        assert_eq!(board.clear_rows(), 2);
+        assert_eq!(board.column_height(0), 0);
+    }
+    #[test]
     fn collide_roof() {
         let board = super::Board::new();
         let mut piece = super::Piece::new(super::Tetromino::I).rotated_left();
@@ -477,15 +631,15 @@ mod tests {
         );
         assert_eq!(
             board.collides(&piece, 2, 9),
-            super::CollisionResult::Unobstructed
+            super::CollisionResult::AboveRoof
         );
         assert_eq!(
             board.collides(&piece, 2, 10),
-            super::CollisionResult::Unobstructed
+            super::CollisionResult::AboveRoof
         );
         assert_eq!(
             board.collides(&piece, 2, 11),
-            super::CollisionResult::Unobstructed
+            super::CollisionResult::AboveRoof
         );
     }
 }
@@ -0,0 +1,225 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Randomizers, which pick the order tetrominos come in.
+//! Every one is seeded, so the same seed deals the same game.
+//! They draw straight from a PCG generator rather than through rand's helpers,
+//! whose output can change between versions, so a seed keeps dealing the same game.
+
+use super::Tetromino;
+use rand::RngCore;
+use rand_pcg::Pcg32;
+use std::collections::VecDeque;
+
+/// PCG's default stream, which every randomizer's generator uses
+const STREAM: u64 = 721_347_520_444_481_703;
+
+/// Returns the generator for a seed
+fn generator(seed: u64) -> Pcg32 {
+    Pcg32::new(seed, STREAM)
+}
+
+/// Returns a number below n.
+/// The modulo is very slightly biased, but not so anyone could tell with 7 pieces.
+fn below(rng: &mut Pcg32, n: usize) -> usize {
+    rng.next_u32() as usize % n
+}
+
+/// Returns one of the choices, picked by rng
+fn choose<T: Copy>(rng: &mut Pcg32, choices: &[T]) -> T {
+    choices[below(rng, choices.len())]
+}
+
+/// Picks tetrominos
+pub trait Randomizer {
+    /// Returns the next tetromino to play
+    fn next(&mut self) -> Tetromino;
+}
+
+/// Deals every tetromino a few times over in a shuffled bag, then refills it.
+/// One of each is the guideline's 7-bag, two is a looser 14-bag.
+#[derive(Clone, Debug)]
+pub struct Bag {
+    rng: Pcg32,
+    copies: usize,
+    bag: Vec<Tetromino>,
+}
+
+impl Bag {
+    /// Returns a bag with `copies` of each tetromino
+    pub fn new(copies: usize, seed: u64) -> Bag {
+        Bag {
+            rng: generator(seed),
+            copies,
+            bag: Vec::new(),
+        }
+    }
+}
+
+impl Randomizer for Bag {
+    fn next(&mut self) -> Tetromino {
+        if self.bag.is_empty() {
+            for _ in 0..self.copies.max(1) {
+                self.bag.extend_from_slice(&Tetromino::ALL);
+            }
+            // Fisher-Yates
+            for i in (1..self.bag.len()).rev() {
+                let j = below(&mut self.rng, i + 1);
+                self.bag.swap(i, j);
+            }
+        }
+        self.bag.pop().unwrap()
+    }
+}
+
+/// TGM's randomizer, which rerolls anything that was one of the last four pieces.
+/// It starts as if it's just dealt four Zs and never starts with an S, Z or O.
+#[derive(Clone, Debug)]
+pub struct History {
+    rng: Pcg32,
+    /// How many times to roll before taking whatever comes up
+    rolls: usize,
+    history: VecDeque<Tetromino>,
+    first: bool,
+}
+
+impl History {
+    /// Returns a history randomizer that rolls up to `rolls` times, 4 in TGM and 6 in TGM2
+    pub fn new(rolls: usize, seed: u64) -> History {
+        History {
+            rng: generator(seed),
+            rolls,
+            history: VecDeque::from(vec![Tetromino::Z; 4]),
+            first: true,
+        }
+    }
+}
+
+impl Randomizer for History {
+    fn next(&mut self) -> Tetromino {
+        let tetromino = if self.first {
+            self.first = false;
+            choose(
+                &mut self.rng,
+                &[Tetromino::J, Tetromino::L, Tetromino::I, Tetromino::T],
+            )
+        } else {
+            let mut tetromino = Tetromino::O;
+            for _ in 0..self.rolls.max(1) {
+                tetromino = choose(&mut self.rng, &Tetromino::ALL);
+                if !self.history.contains(&tetromino) {
+                    break;
+                }
+            }
+            tetromino
+        };
+        self.history.pop_front();
+        self.history.push_back(tetromino);
+        tetromino
+    }
+}
+
+/// Any tetromino, any time, like it used to be
+#[derive(Clone, Debug)]
+pub struct Uniform {
+    rng: Pcg32,
+}
+
+impl Uniform {
+    /// Returns a uniform randomizer
+    pub fn new(seed: u64) -> Uniform {
+        Uniform {
+            rng: generator(seed),
+        }
+    }
+}
+
+impl Randomizer for Uniform {
+    fn next(&mut self) -> Tetromino {
+        choose(&mut self.rng, &Tetromino::ALL)
+    }
+}
+
+/// Returns a randomizer by name: 7bag, 14bag, tgm or random
+pub fn by_name(name: &str, seed: u64) -> Option<Box<dyn Randomizer>> {
+    match name.to_lowercase().as_str() {
+        "7bag" => Some(Box::new(Bag::new(1, seed))),
+        "14bag" => Some(Box::new(Bag::new(2, seed))),
+        "tgm" => Some(Box::new(History::new(4, seed))),
+        "random" => Some(Box::new(Uniform::new(seed))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{by_name, Bag, History, Randomizer};
+    use crate::tetris::Tetromino;
+
+    fn deal(randomizer: &mut dyn Randomizer, n: usize) -> Vec<Tetromino> {
+        (0..n).map(|_| randomizer.next()).collect()
+    }
+
+    #[test]
+    fn bags() {
+        for copies in 1..=2 {
+            let mut bag = Bag::new(copies, 1);
+            for _ in 0..10 {
+                let dealt = deal(&mut bag, 7 * copies);
+                for tetromino in Tetromino::ALL.iter() {
+                    assert_eq!(dealt.iter().filter(|t| *t == tetromino).count(), copies);
+                }
+            }
+        }
+    }
+    #[test]
+    fn history() {
+        let mut history = History::new(4, 2);
+        let dealt = deal(&mut history, 1000);
+        assert!(![Tetromino::S, Tetromino::Z, Tetromino::O].contains(&dealt[0]));
+        // Rerolling makes repeats rare, far rarer than the 1 in 7 of rolling once
+        let repeats = dealt.windows(2).filter(|pair| pair[0] == pair[1]).count();
+        assert!(repeats < 50, "{} repeats", repeats);
+    }
+    #[test]
+    fn seeded() {
+        for name in ["7bag", "14bag", "tgm", "random"].iter() {
+            let mut a = by_name(name, 42).unwrap();
+            let mut b = by_name(name, 42).unwrap();
+            let mut c = by_name(name, 43).unwrap();
+            let dealt = deal(a.as_mut(), 50);
+            assert_eq!(dealt, deal(b.as_mut(), 50));
+            assert_ne!(dealt, deal(c.as_mut(), 50));
+        }
+        assert!(by_name("9bag", 0).is_none());
+    }
+    #[test]
+    fn pinned() {
+        // Recorded seeds have to keep dealing the same game
+        let dealt = |name| {
+            let mut randomizer = by_name(name, 1234).unwrap();
+            deal(randomizer.as_mut(), 14)
+                .iter()
+                .map(|t| format!("{:?}", t))
+                .collect::<String>()
+        };
+        assert_eq!(dealt("7bag"), "SOILTZJLSZITOJ");
+        assert_eq!(dealt("14bag"), "SLTIZITLZOOJSJ");
+        assert_eq!(dealt("tgm"), "JLTOLZJIOLTJSO");
+        assert_eq!(dealt("random"), "SLTOTOLLTLOZJZ");
+    }
+}
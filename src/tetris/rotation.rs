@@ -0,0 +1,328 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Rotation systems, which decide how pieces turn and where they get kicked to when they can't.
+//! Each one describes every piece as cells in a box that it turns inside of,
+//! so pieces turn about the box's centre instead of the corner of their render.
+
+use super::{Piece, Rotation, Tetromino};
+use array2d::Array2D;
+
+/// The cells of a piece in one rotation state, as (x, y) from the bottom left of its box
+pub type Cells = [(usize, usize); 4];
+
+/// Decides how pieces turn.
+/// States count clockwise turns from the state a tetromino spawns in, from 0 to 3.
+pub trait RotationSystem {
+    /// Returns a tetromino's cells in each rotation state
+    fn states(&self, tetromino: Tetromino) -> [Cells; 4];
+    /// Returns the (x, y) nudges to try, in order, when a tetromino turns from one state to another.
+    /// It goes to the first one where it fits, and can't turn if there isn't one.
+    fn kicks(&self, tetromino: Tetromino, from: usize, to: usize) -> Vec<(isize, isize)>;
+}
+
+/// The Super Rotation System from the guideline games, with its wall and floor kicks
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Srs;
+
+/// The Nintendo Rotation System from the NES game.
+/// Nothing kicks, and S, Z and I only have two states.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Nrs;
+
+/// The Arika Rotation System from TGM.
+/// Pieces sit at the bottom of their box, and anything but an I or O
+/// tries one to the right then one to the left if it can't turn where it is.
+/// TGM's rule about blocks in the centre column isn't followed.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Ars;
+
+/// Kicks for each turn, as (from, to) states and the nudges to try
+type KickTable = [((usize, usize), [(isize, isize); 5]); 8];
+
+/// SRS kicks for J, L, S, T and Z, from state to state
+const JLSTZ_KICKS: KickTable = [
+    ((0, 1), [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+    ((1, 0), [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+    ((1, 2), [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]),
+    ((2, 1), [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)]),
+    ((2, 3), [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+    ((3, 2), [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+    ((3, 0), [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]),
+    ((0, 3), [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]),
+];
+/// SRS kicks for I, from state to state
+const I_KICKS: KickTable = [
+    ((0, 1), [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+    ((1, 0), [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+    ((1, 2), [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+    ((2, 1), [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+    ((2, 3), [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)]),
+    ((3, 2), [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)]),
+    ((3, 0), [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)]),
+    ((0, 3), [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)]),
+];
+
+impl RotationSystem for Srs {
+    fn states(&self, tetromino: Tetromino) -> [Cells; 4] {
+        match tetromino {
+            Tetromino::S => all_turns([(1, 2), (2, 2), (0, 1), (1, 1)], 3),
+            Tetromino::J => all_turns([(0, 2), (0, 1), (1, 1), (2, 1)], 3),
+            Tetromino::L => all_turns([(2, 2), (0, 1), (1, 1), (2, 1)], 3),
+            Tetromino::I => all_turns([(0, 2), (1, 2), (2, 2), (3, 2)], 4),
+            Tetromino::T => all_turns([(1, 2), (0, 1), (1, 1), (2, 1)], 3),
+            Tetromino::Z => all_turns([(0, 2), (1, 2), (1, 1), (2, 1)], 3),
+            Tetromino::O => all_turns([(0, 0), (1, 0), (0, 1), (1, 1)], 2),
+        }
+    }
+    fn kicks(&self, tetromino: Tetromino, from: usize, to: usize) -> Vec<(isize, isize)> {
+        let table = match tetromino {
+            Tetromino::O => return vec![(0, 0)],
+            Tetromino::I => &I_KICKS,
+            _ => &JLSTZ_KICKS,
+        };
+        match table.iter().find(|(turn, _)| *turn == (from, to)) {
+            Some((_, kicks)) => kicks.to_vec(),
+            // Half turns don't kick
+            None => vec![(0, 0)],
+        }
+    }
+}
+
+impl RotationSystem for Nrs {
+    fn states(&self, tetromino: Tetromino) -> [Cells; 4] {
+        // Flat side up, turning about the middle of the flat side
+        let two_states = |flat: Cells, upright: Cells| [flat, upright, flat, upright];
+        match tetromino {
+            Tetromino::S => two_states(
+                [(1, 1), (2, 1), (0, 0), (1, 0)],
+                [(1, 2), (1, 1), (2, 1), (2, 0)],
+            ),
+            Tetromino::J => all_turns([(0, 1), (1, 1), (2, 1), (2, 0)], 3),
+            Tetromino::L => all_turns([(0, 1), (1, 1), (2, 1), (0, 0)], 3),
+            Tetromino::I => two_states(
+                [(0, 1), (1, 1), (2, 1), (3, 1)],
+                [(2, 0), (2, 1), (2, 2), (2, 3)],
+            ),
+            Tetromino::T => all_turns([(0, 1), (1, 1), (2, 1), (1, 0)], 3),
+            Tetromino::Z => two_states(
+                [(0, 1), (1, 1), (1, 0), (2, 0)],
+                [(2, 2), (1, 1), (2, 1), (1, 0)],
+            ),
+            Tetromino::O => all_turns([(0, 0), (1, 0), (0, 1), (1, 1)], 2),
+        }
+    }
+    fn kicks(&self, _tetromino: Tetromino, _from: usize, _to: usize) -> Vec<(isize, isize)> {
+        vec![(0, 0)]
+    }
+}
+
+impl RotationSystem for Ars {
+    fn states(&self, tetromino: Tetromino) -> [Cells; 4] {
+        match tetromino {
+            Tetromino::S => {
+                let flat = [(1, 1), (2, 1), (0, 0), (1, 0)];
+                let upright = [(0, 2), (0, 1), (1, 1), (1, 0)];
+                [flat, upright, flat, upright]
+            }
+            Tetromino::J => [
+                [(0, 1), (1, 1), (2, 1), (2, 0)],
+                [(1, 2), (1, 1), (0, 0), (1, 0)],
+                [(0, 1), (0, 0), (1, 0), (2, 0)],
+                [(1, 2), (2, 2), (1, 1), (1, 0)],
+            ],
+            Tetromino::L => [
+                [(0, 1), (1, 1), (2, 1), (0, 0)],
+                [(0, 2), (1, 2), (1, 1), (1, 0)],
+                [(2, 1), (0, 0), (1, 0), (2, 0)],
+                [(1, 2), (1, 1), (1, 0), (2, 0)],
+            ],
+            Tetromino::I => {
+                let flat = [(0, 2), (1, 2), (2, 2), (3, 2)];
+                let upright = [(2, 0), (2, 1), (2, 2), (2, 3)];
+                [flat, upright, flat, upright]
+            }
+            Tetromino::T => [
+                [(0, 1), (1, 1), (2, 1), (1, 0)],
+                [(1, 2), (0, 1), (1, 1), (1, 0)],
+                [(1, 1), (0, 0), (1, 0), (2, 0)],
+                [(1, 2), (1, 1), (2, 1), (1, 0)],
+            ],
+            Tetromino::Z => {
+                let flat = [(0, 1), (1, 1), (1, 0), (2, 0)];
+                let upright = [(2, 2), (1, 1), (2, 1), (1, 0)];
+                [flat, upright, flat, upright]
+            }
+            Tetromino::O => all_turns([(0, 0), (1, 0), (0, 1), (1, 1)], 2),
+        }
+    }
+    fn kicks(&self, tetromino: Tetromino, _from: usize, _to: usize) -> Vec<(isize, isize)> {
+        match tetromino {
+            Tetromino::I | Tetromino::O => vec![(0, 0)],
+            _ => vec![(0, 0), (1, 0), (-1, 0)],
+        }
+    }
+}
+
+/// Returns a rotation system by name: srs, nrs or ars
+pub fn by_name(name: &str) -> Option<Box<dyn RotationSystem>> {
+    match name.to_lowercase().as_str() {
+        "srs" => Some(Box::new(Srs)),
+        "nrs" => Some(Box::new(Nrs)),
+        "ars" => Some(Box::new(Ars)),
+        _ => None,
+    }
+}
+
+/// Returns the moves to try, in order, for a piece that was in rotation `from`
+/// and has just turned to the rotation it's in now.
+/// They move the corner of the piece's render, so it turns about the centre of its box.
+pub fn moves(system: &dyn RotationSystem, piece: &Piece, from: Rotation) -> Vec<(isize, isize)> {
+    let tetromino = piece.tetromino();
+    let states = system.states(tetromino);
+    // Which state a piece with no rotation is in, by matching shapes
+    let unrotated = Piece::new(tetromino).render();
+    let zero = (0..4)
+        .find(|state| render(&states[*state]).0 == unrotated)
+        .expect("rotation system doesn't have a state shaped like the piece");
+    let (from, to) = (
+        (zero + turns(from)) % 4,
+        (zero + turns(piece.rotation())) % 4,
+    );
+    let (from_corner, to_corner) = (render(&states[from]).1, render(&states[to]).1);
+    system
+        .kicks(tetromino, from, to)
+        .into_iter()
+        .map(|(dx, dy)| {
+            (
+                to_corner.0 as isize - from_corner.0 as isize + dx,
+                to_corner.1 as isize - from_corner.1 as isize + dy,
+            )
+        })
+        .collect()
+}
+
+/// Returns how many clockwise turns a rotation is from none
+fn turns(rotation: Rotation) -> usize {
+    match rotation {
+        Rotation::Zero => 0,
+        Rotation::OneHalfPi => 1,
+        Rotation::Pi => 2,
+        Rotation::HalfPi => 3,
+    }
+}
+
+/// Returns one clockwise turn of some cells in a box `size` wide
+fn turned(cells: Cells, size: usize) -> Cells {
+    let mut turned = cells;
+    for (cell, (x, y)) in turned.iter_mut().zip(cells.iter()) {
+        *cell = (*y, size - 1 - x);
+    }
+    turned
+}
+
+/// Returns some cells and their three clockwise turns
+fn all_turns(spawn: Cells, size: usize) -> [Cells; 4] {
+    let right = turned(spawn, size);
+    let half = turned(right, size);
+    [spawn, right, half, turned(half, size)]
+}
+
+/// Renders cells the way Piece does, top to bottom and cropped to fit,
+/// along with where the bottom left corner of that is in the box
+fn render(cells: &Cells) -> (Array2D<bool>, (usize, usize)) {
+    let left = cells.iter().map(|(x, _)| *x).min().unwrap_or(0);
+    let bottom = cells.iter().map(|(_, y)| *y).min().unwrap_or(0);
+    let width = cells.iter().map(|(x, _)| x + 1 - left).max().unwrap_or(0);
+    let height = cells.iter().map(|(_, y)| y + 1 - bottom).max().unwrap_or(0);
+    let mut render = Array2D::filled_with(false, height, width);
+    for (x, y) in cells.iter() {
+        render[(height - 1 - (y - bottom), x - left)] = true;
+    }
+    (render, (left, bottom))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{moves, render, Ars, Nrs, RotationSystem, Srs};
+    use crate::tetris::{Board, Piece, Rotation, Tetromino};
+
+    #[test]
+    fn shapes_match_pieces() {
+        let systems: [&dyn RotationSystem; 3] = [&Srs, &Nrs, &Ars];
+        for system in systems.iter() {
+            for tetromino in Tetromino::ALL.iter() {
+                let states = system.states(*tetromino);
+                let mut piece = Piece::new(*tetromino);
+                let zero = (0..4)
+                    .find(|state| render(&states[*state]).0 == piece.render())
+                    .unwrap();
+                for turn in 1..4 {
+                    piece.rotate_right();
+                    assert_eq!(render(&states[(zero + turn) % 4]).0, piece.render());
+                }
+            }
+        }
+    }
+    #[test]
+    fn srs_turns_in_place() {
+        // An upright I is in the third column of its box, and lies down in the second row
+        let piece = Piece::new(Tetromino::I).rotated_right();
+        assert_eq!(moves(&Srs, &piece, Rotation::Zero)[0], (-2, 1));
+        // And four turns is back where it started
+        let mut piece = Piece::new(Tetromino::I);
+        let (mut x, mut y) = (0, 0);
+        for _ in 0..4 {
+            let from = piece.rotation();
+            piece.rotate_left();
+            let (dx, dy) = moves(&Srs, &piece, from)[0];
+            x += dx;
+            y += dy;
+        }
+        assert_eq!((x, y), (0, 0));
+    }
+    #[test]
+    fn srs_wall_kick() {
+        let board = Board::with_size(10, 20);
+        // An upright I against the left wall has to kick right to lie down,
+        // the third kick from R to 2
+        let turned = Piece::new(Tetromino::I).rotated_right();
+        assert_eq!(
+            board.try_rotation(&Srs, &turned, Rotation::Zero, 0, 5),
+            Some((0, 6))
+        );
+        // Nothing kicks in NRS
+        assert_eq!(
+            board.try_rotation(&Nrs, &turned, Rotation::Zero, 0, 5),
+            None
+        );
+    }
+    #[test]
+    fn ars_kicks_sideways() {
+        let board = Board::with_size(10, 20);
+        // An upright J against the right wall has to kick left to lie down
+        let turned = Piece::new(Tetromino::J).rotated_right();
+        assert_eq!(
+            board.try_rotation(&Ars, &turned, Rotation::Zero, 8, 0),
+            Some((7, 0))
+        );
+        assert_eq!(
+            board.try_rotation(&Nrs, &turned, Rotation::Zero, 8, 0),
+            None
+        );
+    }
+}
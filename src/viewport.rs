@@ -0,0 +1,253 @@
+/*
+ * --------------------
+ * THIS FILE IS LICENSED UNDER THE FOLLOWING TERMS
+ *
+ * this code may not be used for any purpose. be gay, do crime
+ *
+ * THE FOLLOWING MESSAGE IS NOT A LICENSE
+ *
+ * <barrow@tilde.team> wrote this file.
+ * by reading this text, you are reading "TRANS RIGHTS".
+ * this file and the content within it is the gay agenda.
+ * if we meet some day, and you think this stuff is worth it,
+ * you can buy me a beer, tea, or something stronger.
+ * -Ezra Barrow
+ * --------------------
+ */
+
+//! Showing part of a well that's bigger than the display.
+
+use array2d::Array2D;
+use std::time::{Duration, Instant};
+
+/// A rectangle in well coordinates, x from the left and y from the bottom like Pad
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Which sides of the well have something lit that the viewport can't show
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Offscreen {
+    pub above: bool,
+    pub below: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+/// A display-sized window onto a bigger well that follows the falling piece.
+/// The view keeps the whole piece in sight, with as much of the well under it as fits,
+/// and slides there a cell at a time so the player can see where it's going.
+/// Wells that fit on the display are shown from the bottom left corner, padded with 0.
+/// ```
+/// # use lp_tetris::viewport::{Rect, Viewport};
+/// # use array2d::Array2D;
+/// # use std::time::Instant;
+/// let mut viewport = Viewport::new(8, 8);
+/// let piece = Rect { x: 4, y: 17, width: 2, height: 3 };
+/// // The first follow jumps straight there
+/// viewport.follow((10, 20), piece, 0, Instant::now());
+/// assert_eq!(viewport.offset(), (0, 12));
+/// let well = Array2D::filled_by_row_major(|| 1, 20, 10);
+/// assert_eq!(viewport.crop(&well).num_rows(), 8);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Viewport {
+    width: usize,
+    height: usize,
+    /// Bottom left corner of the view, in well coordinates
+    x: usize,
+    y: usize,
+    /// How long it takes to scroll one cell
+    step: Duration,
+    /// When it last scrolled, None until the first follow
+    last_step: Option<Instant>,
+}
+
+impl Viewport {
+    /// Returns a viewport the size of the display, scrolling a cell every 50ms
+    pub fn new(width: usize, height: usize) -> Viewport {
+        Viewport {
+            width,
+            height,
+            x: 0,
+            y: 0,
+            step: Duration::from_millis(50),
+            last_step: None,
+        }
+    }
+    /// Sets how long it takes to scroll one cell
+    pub fn set_step(&mut self, step: Duration) {
+        self.step = step;
+    }
+    /// Returns the well coordinates of the view's bottom left corner
+    pub fn offset(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+    /// Moves towards wherever shows the piece best.
+    /// `well` is the well's (width, height) and `stack` is how high the stack is under the piece.
+    /// The piece itself is never allowed out of view, however fast it moves.
+    pub fn follow(&mut self, well: (usize, usize), piece: Rect, stack: usize, now: Instant) {
+        let (max_x, max_y) = (
+            well.0.saturating_sub(self.width),
+            well.1.saturating_sub(self.height),
+        );
+        // Just far enough sideways to see the piece
+        let target_x = if piece.x < self.x {
+            piece.x
+        } else if piece.x + piece.width > self.x + self.width {
+            piece.x + piece.width - self.width
+        } else {
+            self.x
+        };
+        // The top of the view on the piece, or just above the stack if the piece has
+        // slid into a gap lower down. Either way the bottom of the piece stays in view.
+        let top = (piece.y + piece.height).max(stack + 1);
+        let target_y = top.saturating_sub(self.height).min(piece.y);
+        let (target_x, target_y) = (target_x.min(max_x), target_y.min(max_y));
+
+        match self.last_step {
+            None => {
+                self.x = target_x;
+                self.y = target_y;
+                self.last_step = Some(now);
+            }
+            Some(last) if now >= last + self.step => {
+                self.x = step_towards(self.x, target_x);
+                self.y = step_towards(self.y, target_y);
+                self.last_step = Some(now);
+            }
+            Some(_) => (),
+        }
+        // Never lose the piece, even mid-scroll
+        self.x = self
+            .x
+            .max((piece.x + piece.width).saturating_sub(self.width))
+            .min(piece.x)
+            .min(max_x);
+        self.y = self
+            .y
+            .max((piece.y + piece.height).saturating_sub(self.height))
+            .min(piece.y)
+            .min(max_y);
+    }
+    /// Cuts the view out of a frame of the whole well
+    pub fn crop<T: Clone + Default>(&self, well: &Array2D<T>) -> Array2D<T> {
+        let mut view = Array2D::filled_with(T::default(), self.height, self.width);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if let Some(cell) = well.get(self.y + y, self.x + x) {
+                    view[(y, x)] = cell.clone();
+                }
+            }
+        }
+        view
+    }
+    /// Returns which sides of a frame of the whole well have something lit out of view
+    pub fn offscreen(&self, well: &Array2D<u8>) -> Offscreen {
+        let mut offscreen = Offscreen::default();
+        for y in 0..well.num_rows() {
+            for x in 0..well.num_columns() {
+                if well[(y, x)] == 0 {
+                    continue;
+                }
+                offscreen.below |= y < self.y;
+                offscreen.above |= y >= self.y + self.height;
+                offscreen.left |= x < self.x;
+                offscreen.right |= x >= self.x + self.width;
+            }
+        }
+        offscreen
+    }
+}
+
+/// Moves one step from `from` towards `to`
+fn step_towards(from: usize, to: usize) -> usize {
+    if from < to {
+        from + 1
+    } else if from > to {
+        from - 1
+    } else {
+        from
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Offscreen, Rect, Viewport};
+    use array2d::Array2D;
+    use std::time::{Duration, Instant};
+
+    fn piece(x: usize, y: usize) -> Rect {
+        Rect {
+            x,
+            y,
+            width: 2,
+            height: 2,
+        }
+    }
+
+    #[test]
+    fn scrolls_a_cell_at_a_time() {
+        let start = Instant::now();
+        let ms = |n| start + Duration::from_millis(n);
+        let mut viewport = Viewport::new(8, 8);
+        viewport.follow((10, 20), piece(0, 10), 0, start);
+        assert_eq!(viewport.offset(), (0, 4));
+        // The piece pulls the view along with it
+        viewport.follow((10, 20), piece(0, 3), 0, ms(10));
+        assert_eq!(viewport.offset(), (0, 3));
+        // Over to the right, one column per step
+        viewport.follow((10, 20), piece(8, 3), 0, ms(20));
+        assert_eq!(viewport.offset(), (2, 3));
+        viewport.follow((10, 20), piece(6, 3), 0, ms(100));
+        assert_eq!(viewport.offset(), (2, 2));
+        viewport.follow((10, 20), piece(6, 3), 0, ms(120));
+        assert_eq!(viewport.offset(), (2, 2));
+        viewport.follow((10, 20), piece(6, 3), 0, ms(150));
+        assert_eq!(viewport.offset(), (2, 1));
+    }
+    #[test]
+    fn stack_above_piece() {
+        let mut viewport = Viewport::new(8, 8);
+        // A piece that's slid under an overhang stays in view, with the overhang above it
+        viewport.follow((10, 20), piece(0, 2), 12, Instant::now());
+        assert_eq!(viewport.offset(), (0, 2));
+        let mut viewport = Viewport::new(8, 8);
+        viewport.follow((10, 20), piece(0, 2), 6, Instant::now());
+        assert_eq!(viewport.offset(), (0, 0));
+    }
+    #[test]
+    fn small_well() {
+        let mut viewport = Viewport::new(8, 8);
+        viewport.follow((6, 6), piece(4, 4), 0, Instant::now());
+        assert_eq!(viewport.offset(), (0, 0));
+        let well = Array2D::filled_with(1u8, 6, 6);
+        let view = viewport.crop(&well);
+        assert_eq!(view[(5, 5)], 1);
+        assert_eq!(view[(6, 0)], 0);
+        assert_eq!(view[(0, 6)], 0);
+        assert_eq!(viewport.offscreen(&well), Offscreen::default());
+    }
+    #[test]
+    fn offscreen() {
+        let mut viewport = Viewport::new(8, 8);
+        viewport.follow((10, 20), piece(2, 10), 0, Instant::now());
+        let mut well = Array2D::filled_with(0u8, 20, 10);
+        assert_eq!(viewport.offscreen(&well), Offscreen::default());
+        well[(0, 9)] = 5;
+        well[(19, 0)] = 5;
+        assert_eq!(
+            viewport.offscreen(&well),
+            Offscreen {
+                above: true,
+                below: true,
+                left: false,
+                right: true
+            }
+        );
+    }
+}